@@ -0,0 +1,343 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Not, Shl, Shr, Sub};
+use std::str::FromStr;
+
+/// The most bits a power may have, as `**` could otherwise be
+/// asked for a number big enough to hang the interpreter
+pub const MAX_POW_BITS: u64 = 1 << 16;
+
+/// An integer of any size, kept as a sign and the magnitude's
+/// 32 bit digits with the least significant first. There are
+/// never any leading zero digits, and zero is never negative
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct BigInt {
+    negative: bool,
+    mag: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut mag: Vec<u32>) -> BigInt {
+        while mag.last() == Some(&0) {
+            mag.pop();
+        }
+        BigInt { negative: negative && !mag.is_empty(), mag }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::new(false, self.mag.clone())
+    }
+
+    /// The value as an i64, if it fits in one
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+        let mag = self.mag.iter().rev().fold(0i128, |acc, &digit| (acc << 32) | digit as i128);
+        i64::try_from(if self.negative { -mag } else { mag }).ok()
+    }
+
+    /// The nearest float, which may be infinite
+    pub fn to_f64(&self) -> f64 {
+        let mag = self.mag.iter().rev().fold(0.0, |acc, &digit| acc * 4294967296.0 + digit as f64);
+        if self.negative { -mag } else { mag }
+    }
+
+    /// Divide, rounding towards zero as i64 division does, and
+    /// give the quotient along with the remainder, which takes
+    /// the sign of `self`. None when dividing by zero
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (quot, rem) = div_rem_mag(&self.mag, &other.mag);
+        Some((
+            BigInt::new(self.negative != other.negative, quot),
+            BigInt::new(self.negative, rem),
+        ))
+    }
+
+    /// The greatest common divisor, which is never negative
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while !b.is_zero() {
+            let rem = a.div_rem(&b).expect("Checked for zero!").1;
+            (a, b) = (b, rem);
+        }
+        a
+    }
+
+    /// How many bits the magnitude takes up
+    pub fn bits(&self) -> u64 {
+        match self.mag.last() {
+            Some(top) => self.mag.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// Raise to a power, or None if the result could take up
+    /// more than `MAX_POW_BITS` bits
+    pub fn checked_pow(&self, exp: u64) -> Option<BigInt> {
+        // 0, 1 and -1 stay small whatever the power
+        if self.bits() <= 1 {
+            let negative = self.negative && exp % 2 == 1;
+            return Some(if exp == 0 { BigInt::from(1i64) } else { BigInt::new(negative, self.mag.clone()) });
+        }
+        if self.bits().saturating_mul(exp) > MAX_POW_BITS {
+            return None;
+        }
+        Some(self.pow(exp as u32))
+    }
+
+    fn pow(&self, mut exp: u32) -> BigInt {
+        let (mut base, mut res) = (self.clone(), BigInt::from(1i64));
+        while exp > 0 {
+            if exp % 2 == 1 {
+                res = &res * &base;
+            }
+            base = &base * &base;
+            exp /= 2;
+        }
+        res
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(val: i64) -> BigInt {
+        BigInt::from(val as i128)
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(val: i128) -> BigInt {
+        let mut mag = Vec::new();
+        let mut rest = val.unsigned_abs();
+        while rest > 0 {
+            mag.push(rest as u32);
+            rest >>= 32;
+        }
+        BigInt::new(val < 0, mag)
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ();
+
+    /// Read a decimal integer, with an optional sign
+    fn from_str(val: &str) -> Result<BigInt, ()> {
+        let (negative, digits) = match val.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, val.strip_prefix('+').unwrap_or(val)),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(());
+        }
+
+        let mut mag = Vec::new();
+        for digit in digits.bytes() {
+            mul_small(&mut mag, 10, (digit - b'0') as u32);
+        }
+        Ok(BigInt::new(negative, mag))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // peel off 9 decimal digits at a time
+        let mut chunks = Vec::new();
+        let mut rest = self.mag.clone();
+        while !rest.is_empty() {
+            chunks.push(div_small(&mut rest, 1_000_000_000));
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{}", first)?;
+                rest.iter().rev().try_for_each(|chunk| write!(f, "{:09}", chunk))
+            },
+        }
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.mag)
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_mag(&self.mag, &other.mag));
+        }
+        // the signs differ, so take the smaller magnitude from
+        // the larger, and keep the sign of the larger
+        match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Less => BigInt::new(other.negative, sub_mag(&other.mag, &self.mag)),
+            _ => BigInt::new(self.negative, sub_mag(&self.mag, &other.mag)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other.clone()
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut mag = vec![0u32; self.mag.len() + other.mag.len()];
+        for (i, &a) in self.mag.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.mag.iter().enumerate() {
+                let cur = mag[i + j] as u64 + a as u64 * b as u64 + carry;
+                mag[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            mag[i + other.mag.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, mag)
+    }
+}
+
+/// As for i64, `!x` is `-x - 1`
+impl Not for &BigInt {
+    type Output = BigInt;
+
+    fn not(self) -> BigInt {
+        &-self.clone() - &BigInt::from(1i64)
+    }
+}
+
+impl Shl<u32> for &BigInt {
+    type Output = BigInt;
+
+    fn shl(self, count: u32) -> BigInt {
+        self * &BigInt::from(1i128 << count)
+    }
+}
+
+/// Rounds towards negative infinity, as `>>` does for i64
+impl Shr<u32> for &BigInt {
+    type Output = BigInt;
+
+    fn shr(self, count: u32) -> BigInt {
+        let (quot, rem) = self.div_rem(&BigInt::from(1i128 << count)).expect("A power of 2 is never zero!");
+        if rem.is_negative() { &quot - &BigInt::from(1i64) } else { quot }
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let cur = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        res.push(cur as u32);
+        carry = cur >> 32;
+    }
+    res.push(carry as u32);
+    res
+}
+
+/// `a - b`, where `a` is at least as big as `b`
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &digit) in a.iter().enumerate() {
+        let mut cur = digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = (cur < 0) as i64;
+        if cur < 0 {
+            cur += 1 << 32;
+        }
+        res.push(cur as u32);
+    }
+    res
+}
+
+/// `mag * factor + add`, in place
+fn mul_small(mag: &mut Vec<u32>, factor: u32, add: u32) {
+    let mut carry = add as u64;
+    for digit in mag.iter_mut() {
+        let cur = *digit as u64 * factor as u64 + carry;
+        *digit = cur as u32;
+        carry = cur >> 32;
+    }
+    if carry > 0 {
+        mag.push(carry as u32);
+    }
+}
+
+/// Divide in place, giving the remainder
+fn div_small(mag: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut rem = 0u64;
+    for digit in mag.iter_mut().rev() {
+        let cur = (rem << 32) | *digit as u64;
+        *digit = (cur / divisor as u64) as u32;
+        rem = cur % divisor as u64;
+    }
+    while mag.last() == Some(&0) {
+        mag.pop();
+    }
+    rem as u32
+}
+
+/// Long division of the magnitudes a bit at a time, which is
+/// slow but plenty for the sizes a script will reach
+fn div_rem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quot = vec![0u32; a.len()];
+    let mut rem: Vec<u32> = Vec::new();
+    for bit in (0..a.len() * 32).rev() {
+        // rem = rem * 2 + the next bit of a
+        mul_small(&mut rem, 2, (a[bit / 32] >> (bit % 32)) & 1);
+        if cmp_mag(&rem, b) != Ordering::Less {
+            rem = sub_mag(&rem, b);
+            while rem.last() == Some(&0) {
+                rem.pop();
+            }
+            quot[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (quot, rem)
+}
@@ -0,0 +1,77 @@
+use crate::error::ExecError;
+
+/// The exit codes of the binary, so that scripts run from a
+/// shell or Makefile can tell why they failed
+pub const SUCCESS: i32 = 0;
+/// a stack error while running, or a failing case for `test-all`
+pub const RUNTIME_ERROR: i32 = 1;
+/// the script could not be split into lexemes
+pub const PARSE_ERROR: i32 = 2;
+/// the arguments could not be understood
+pub const USAGE_ERROR: i32 = 64;
+/// a file could not be read or written
+pub const IO_ERROR: i32 = 74;
+
+pub const USAGE: &str = "\
+Usage: swen431-a1-rust [COMMAND]
+
+Commands:
+  (none)          Start an interactive REPL
+  run <path>      Run a script file and print the final stack
+  run -           Run a script read from stdin
+  NNN             Run input/input-NNN.txt and compare it to expected/expected-NNN.txt
+  test-all        Run every input/expected pair and print a summary
+  output NNN      Run input/input-NNN.txt and write output/output-NNN.txt
+
+Options:
+  -h, --help      Print this help
+
+Exit codes:
+  0 success, 1 runtime error (or failing cases), 2 parse error,
+  64 bad arguments, 74 file error";
+
+/// What the binary has been asked to do
+#[derive(PartialEq, Debug)]
+pub enum Command {
+    Repl,
+    Help,
+    /// run a script, `-` for stdin
+    Run(String),
+    /// compare a single numbered test case
+    Case(String),
+    TestAll,
+    Output(String),
+}
+
+/// Parse the arguments, not including the program name
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let args = args.iter().map(String::as_str).collect::<Vec<&str>>();
+    if args.iter().any(|arg| *arg == "-h" || *arg == "--help") {
+        return Ok(Command::Help);
+    }
+
+    match args.as_slice() {
+        [] => Ok(Command::Repl),
+        ["run", path] => Ok(Command::Run(path.to_string())),
+        ["run"] => Err("run expects a path, or - for stdin".to_string()),
+        ["test-all"] => Ok(Command::TestAll),
+        ["output", fnum] if is_case_number(fnum) => Ok(Command::Output(fnum.to_string())),
+        ["output", ..] => Err("output expects a 3 digit test case number".to_string()),
+        [fnum] if is_case_number(fnum) => Ok(Command::Case(fnum.to_string())),
+        [arg, ..] if arg.starts_with('-') => Err(format!("Unknown option '{}'", arg)),
+        _ => Err(format!("Unknown command '{}'", args.join(" "))),
+    }
+}
+
+/// Whether the argument names a test case, i.e. `001`
+fn is_case_number(arg: &str) -> bool {
+    arg.len() == 3 && arg.chars().all(|ch| ch.is_ascii_digit())
+}
+
+/// The exit code for a script that failed to run
+pub fn exit_code(err: &ExecError) -> i32 {
+    match err {
+        ExecError::Lex(_) => PARSE_ERROR,
+        ExecError::Stack(_) => RUNTIME_ERROR,
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+
+use crate::lexer::{LexError, Lexeme};
+use crate::linalg::DimensionError;
+
+/// Everything that can go wrong while executing a stack
+#[derive(PartialEq, Clone, Debug)]
+pub enum StackError {
+    /// an operator needed more elements than the stack holds
+    Underflow { needed: usize, found: usize },
+    /// an operator was given a value of a type it does not support
+    TypeMismatch(String),
+    DivisionByZero,
+    /// an integer result too big for an i64, under the
+    /// `OverflowPolicy::Error` policy
+    Overflow,
+    /// a lexeme that is neither a value nor a known operator
+    UnknownWord(String),
+    /// a value of the right type that the operator cannot use,
+    /// i.e. a negative ROLL count
+    BadArgument(String),
+    /// vectors or matrices of incompatible shapes
+    Dimension(DimensionError),
+    /// FETCH of a variable that has not been stored
+    UndefinedVariable(String),
+    /// a malformed `: NAME ... ;` word definition or control
+    /// structure, i.e. an IF without a THEN
+    Syntax(String),
+    /// lambdas or words recursed past the given depth limit
+    DepthExceeded(usize),
+    /// an error raised by the lexeme, which knows where it
+    /// was in the source
+    Located { lexeme: Lexeme, error: Box<StackError> },
+}
+
+impl StackError {
+    /// Attach the lexeme that raised the error, keeping the
+    /// innermost location if one has already been attached
+    pub fn at(self, lexeme: &Lexeme) -> StackError {
+        match self {
+            located @ StackError::Located { .. } => located,
+            error => StackError::Located {
+                lexeme: lexeme.clone(),
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// The lexeme that raised the error, if it is known
+    pub fn lexeme(&self) -> Option<&Lexeme> {
+        match self {
+            StackError::Located { lexeme, .. } => Some(lexeme),
+            _ => None,
+        }
+    }
+
+    /// The error without any location attached
+    pub fn kind(&self) -> &StackError {
+        match self {
+            StackError::Located { error, .. } => error.kind(),
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for StackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StackError::Underflow { needed, found } =>
+                write!(f, "Stack underflow: needed {} elements but found {}!", needed, found),
+            StackError::TypeMismatch(msg) => write!(f, "{}", msg),
+            StackError::DivisionByZero => write!(f, "Cannot divide by 0!"),
+            StackError::Overflow => write!(f, "Integer overflow!"),
+            StackError::UnknownWord(word) => write!(f, "Unsupported stack element: '{}'", word),
+            StackError::BadArgument(msg) => write!(f, "{}", msg),
+            StackError::Dimension(err) => write!(f, "{}", err),
+            StackError::UndefinedVariable(name) => write!(f, "Variable '{}' is not defined!", name),
+            StackError::Syntax(msg) => write!(f, "{}", msg),
+            StackError::DepthExceeded(max) =>
+                write!(f, "Exceeded the maximum call depth of {}!", max),
+            StackError::Located { lexeme, error } =>
+                write!(f, "{} (at '{}', line {}, column {})", error, lexeme.text, lexeme.line, lexeme.col),
+        }
+    }
+}
+
+impl std::error::Error for StackError {}
+
+impl From<DimensionError> for StackError {
+    fn from(err: DimensionError) -> Self {
+        StackError::Dimension(err)
+    }
+}
+
+/// An error from running a script, either while splitting it
+/// into lexemes or while executing them
+#[derive(PartialEq, Clone, Debug)]
+pub enum ExecError {
+    Lex(LexError),
+    Stack(StackError),
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecError::Lex(err) => write!(f, "Parse error: {}", err),
+            ExecError::Stack(err) => write!(f, "Runtime error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+impl ExecError {
+    /// The error followed by the line of the source it was
+    /// raised at, with the offending lexeme underlined
+    /// 
+    /// ```text
+    /// Runtime error: Cannot divide by 0! (at '/', line 2, column 3)
+    ///   |
+    /// 2 | 1 0 /
+    ///   |     ^
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let (line, col, width) = match self {
+            ExecError::Lex(err) => (err.line, err.col, 1),
+            ExecError::Stack(err) => match err.lexeme() {
+                // the lexeme may be from earlier source, i.e. the body
+                // of a word defined on a previous line of the REPL
+                Some(lexeme) if source.get(lexeme.span.clone()) != Some(lexeme.text.as_str()) => {
+                    return self.to_string();
+                },
+                Some(lexeme) => {
+                    let first_line = lexeme.text.lines().next().unwrap_or_default();
+                    (lexeme.line, lexeme.col, first_line.chars().count().max(1))
+                },
+                None => return self.to_string(),
+            },
+        };
+        let Some(text) = source.lines().nth(line - 1) else {
+            return self.to_string();
+        };
+
+        // keep any tabs so that the carets line up beneath them
+        let indent = text.chars()
+            .take(col - 1)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let gutter = " ".repeat(line.to_string().len());
+        format!(
+            "{}\n{} |\n{} | {}\n{} | {}{}",
+            self, gutter, line, text, gutter, indent, "^".repeat(width)
+        )
+    }
+}
+
+impl From<LexError> for ExecError {
+    fn from(err: LexError) -> Self {
+        ExecError::Lex(err)
+    }
+}
+
+impl From<StackError> for ExecError {
+    fn from(err: StackError) -> Self {
+        ExecError::Stack(err)
+    }
+}
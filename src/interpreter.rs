@@ -0,0 +1,141 @@
+use crate::error::{ExecError, StackError};
+use crate::lexer::{self, Lexeme};
+use crate::overflow::OverflowPolicy;
+use crate::registry::{RegistryError, StackEffect};
+use crate::stack::{self, Env, DEFAULT_MAX_DEPTH};
+use crate::utils::Token;
+
+/// An interpreter that owns its stack, so that values are kept
+/// between calls to `eval`
+///
+/// ```
+/// use swen431_a1_rust::{Interpreter, Token};
+///
+/// let mut interp = Interpreter::new();
+/// interp.push(Token::Int(2));
+/// interp.eval("3 *").unwrap();
+/// assert_eq!(interp.stack(), &[Token::Int(6)]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Interpreter {
+    stack: Vec<Token>,
+    env: Env,
+    max_depth: usize,
+    overflow: OverflowPolicy,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter::with_max_depth(DEFAULT_MAX_DEPTH)
+    }
+
+    /// An interpreter that stops lambdas recursing deeper
+    /// than `max_depth`
+    pub fn with_max_depth(max_depth: usize) -> Interpreter {
+        Interpreter { stack: Vec::new(), env: Env::default(), max_depth, overflow: OverflowPolicy::default() }
+    }
+
+    /// Choose what the integer operators do when a result does
+    /// not fit in an i64, by default it becomes a big int
+    ///
+    /// ```
+    /// use swen431_a1_rust::{Interpreter, OverflowPolicy, Token};
+    ///
+    /// let mut interp = Interpreter::new();
+    /// interp.set_overflow(OverflowPolicy::Saturate);
+    /// interp.eval("9223372036854775807 1 +").unwrap();
+    /// assert_eq!(interp.stack(), &[Token::Int(i64::MAX)]);
+    /// ```
+    pub fn set_overflow(&mut self, overflow: OverflowPolicy) {
+        stack::register_arithmetic(&mut self.env.ops, overflow);
+        self.overflow = overflow;
+    }
+
+    pub fn overflow(&self) -> OverflowPolicy {
+        self.overflow
+    }
+
+    /// Run the source on top of the current stack. If anything
+    /// goes wrong the stack, the defined words and the variables
+    /// are left as they were before the call
+    pub fn eval(&mut self, source: &str) -> Result<(), ExecError> {
+        let lexemes = lexer::read_stack(source)?;
+
+        let before = (self.stack.clone(), self.env.words.clone(), self.env.vars.clone());
+        stack::exec_on_stack(&mut self.stack, &lexemes, &mut self.env, self.max_depth).map_err(|e| {
+            (self.stack, self.env.words, self.env.vars) = before;
+            e.into()
+        })
+    }
+
+    /// Add an operator implemented in Rust, alongside the
+    /// built-ins. See `Registry::register`
+    ///
+    /// ```
+    /// use swen431_a1_rust::{Interpreter, Token};
+    ///
+    /// let mut interp = Interpreter::new();
+    /// interp.register("DEPTH", 0, 1, |stack| {
+    ///     stack.push(Token::Int(stack.len() as i64));
+    ///     Ok(())
+    /// }).unwrap();
+    ///
+    /// interp.eval("1 1 DEPTH").unwrap();
+    /// assert_eq!(interp.stack(), &[Token::Int(1), Token::Int(1), Token::Int(2)]);
+    /// ```
+    pub fn register(
+        &mut self,
+        name: &str,
+        inputs: usize,
+        outputs: usize,
+        handler: impl Fn(&mut Vec<Token>) -> Result<(), StackError> + 'static
+    ) -> Result<(), RegistryError> {
+        if self.env.words.contains_key(name) {
+            return Err(RegistryError::AlreadyDefined(name.to_string()));
+        }
+        self.env.ops.register(name, inputs, outputs, handler)
+    }
+
+    /// Every operator that can be applied and its stack effect
+    pub fn words(&self) -> impl Iterator<Item = (&str, StackEffect)> {
+        self.env.ops.words()
+    }
+
+    /// Every word defined by `: NAME ... ;` and its body
+    pub fn definitions(&self) -> impl Iterator<Item = (&str, &[Lexeme])> {
+        self.env.words.iter().map(|(name, body)| (name.as_str(), &**body))
+    }
+
+    /// The value of a global variable
+    pub fn var(&self, name: &str) -> Option<&Token> {
+        self.env.vars.get(name)
+    }
+
+    pub fn push(&mut self, token: Token) {
+        self.stack.push(token);
+    }
+
+    pub fn pop(&mut self) -> Option<Token> {
+        self.stack.pop()
+    }
+
+    /// The stack with the top last
+    pub fn stack(&self) -> &[Token] {
+        &self.stack
+    }
+
+    /// Swap in a new stack, giving back the old one
+    pub fn replace_stack(&mut self, stack: Vec<Token>) -> Vec<Token> {
+        std::mem::replace(&mut self.stack, stack)
+    }
+
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+}
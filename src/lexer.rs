@@ -1,28 +1,292 @@
-use crate::utils::{parse_bool, parse_string, Token};
+use std::fmt;
+use std::iter::Peekable;
+use std::ops::Range;
 
-pub fn read_stack(fstr: &String) -> Vec<String> {
-    let lexemes = fstr
-        .lines()
-        .map(|line| {
-            line.split_whitespace()
-                .map(|val| val.to_string())
-                .collect::<Vec<String>>()
-        })
-        .flat_map(|line| line)
-        .collect::<Vec<String>>();
+use crate::bigint::BigInt;
+use crate::rational::Rational;
+use crate::utils::{parse_bool, parse_bracketed, parse_lambda, parse_string, unescape_string, Token};
 
-    lexemes
+/// An error found while splitting the input into lexemes,
+/// along with the line and column (both from 1) it occurred at
+#[derive(PartialEq, Clone, Debug)]
+pub struct LexError {
+    pub message: String,
+    pub line: usize,
+    pub col: usize,
 }
 
-pub fn parse_data_type(val: &String) -> Option<Token> {
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message, self.line, self.col)
+    }
+}
+
+/// A lexeme along with where it was found in the source, the
+/// line and column (both from 1) of its first char and the
+/// byte range it covers
+#[derive(PartialEq, Clone, Debug)]
+pub struct Lexeme {
+    pub text: String,
+    pub line: usize,
+    pub col: usize,
+    pub span: Range<usize>,
+    pub kind: LexemeKind,
+}
+
+/// Whether a lexeme is executed, or is trivia kept only so that
+/// the source can be written back out
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum LexemeKind {
+    Code,
+    /// `# ...` or `// ...` to the end of the line, or `( ... )`
+    Comment,
+}
+
+impl Lexeme {
+    pub fn is_trivia(&self) -> bool {
+        self.kind != LexemeKind::Code
+    }
+
+    /// Where a lexeme read from `inner` would be in the source,
+    /// given that `inner` follows `prefix` at the start of `self`
+    fn relocate(&self, prefix: &str, inner: Lexeme) -> Lexeme {
+        let (mut line, mut col) = (self.line, self.col);
+        for ch in prefix.chars() {
+            if ch == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        let offset = self.span.start + prefix.len();
+
+        Lexeme {
+            col: if inner.line == 1 { col + inner.col - 1 } else { inner.col },
+            line: line + inner.line - 1,
+            span: inner.span.start + offset..inner.span.end + offset,
+            text: inner.text,
+            kind: inner.kind,
+        }
+    }
+}
+
+/// Split the input into lexemes on whitespace, except that
+/// a bracketed literal such as `[1, 2, 3]` or a lambda such
+/// as `{1 | x0 DUP}` is kept together as a single lexeme
+/// (brackets and braces may nest), as is a quoted string
+/// such as `"Hello World"`. Comments are kept as trivia
+pub fn read_stack(fstr: &str) -> Result<Vec<Lexeme>, LexError> {
+    let mut lexemes = Vec::new();
+    let mut current: Option<Lexeme> = None;
+    // the brackets and braces not yet closed, and where they were
+    let mut open: Vec<(char, usize, usize)> = Vec::new();
+    let mut chars = with_positions(fstr).peekable();
+    let mut after_space = true;
+
+    while let Some((ch, line, col, offset)) = chars.next() {
+        let starts_word = std::mem::replace(&mut after_space, ch.is_whitespace());
+        if ch.is_whitespace() && open.is_empty() {
+            lexemes.extend(current.take());
+            continue;
+        }
+
+        let next = chars.peek().map(|&(next, ..)| next);
+        if let Some(close) = comment_close(ch, next).filter(|_| starts_word) {
+            let mut comment = Lexeme {
+                text: ch.to_string(),
+                line,
+                col,
+                span: offset..offset + ch.len_utf8(),
+                kind: LexemeKind::Comment,
+            };
+            read_comment(&mut chars, &mut comment, close)?;
+            // a `//` with nothing after it is more likely an attempt
+            // at exact division than a comment
+            if comment.text.trim_end() == "//" {
+                let message = "Empty // comment, exact divide is RDIV".to_string();
+                return Err(LexError { message, line, col });
+            }
+            match &mut current {
+                // within a lambda or vector the comment is kept as a
+                // part of it, to be read again along with its body
+                Some(lexeme) => {
+                    lexeme.text.push_str(&comment.text);
+                    lexeme.span.end = comment.span.end;
+                },
+                None => lexemes.push(comment),
+            }
+            continue;
+        }
+
+        let lexeme = current.get_or_insert_with(|| Lexeme {
+            text: String::new(),
+            line,
+            col,
+            span: offset..offset,
+            kind: LexemeKind::Code,
+        });
+        lexeme.text.push(ch);
+        lexeme.span.end = offset + ch.len_utf8();
+
+        match ch {
+            '"' => lexeme.span.end = read_string(&mut chars, &mut lexeme.text, line, col)?,
+            '[' | '{' => open.push((ch, line, col)),
+            ']' | '}' => {
+                open.pop();
+            },
+            _ => (),
+        }
+    }
+    if let Some((ch, line, col)) = open.pop() {
+        let message = if ch == '{' { "Unterminated lambda" } else { "Unterminated vector" };
+        return Err(LexError { message: message.to_string(), line, col });
+    }
+    lexemes.extend(current);
+
+    Ok(lexemes)
+}
+
+/// The char that ends a comment starting with `ch`, a newline
+/// for `#` and `//`, or `)` for a `( ... )` stack effect comment
+fn comment_close(ch: char, next: Option<char>) -> Option<char> {
+    match (ch, next) {
+        ('#', _) | ('/', Some('/')) => Some('\n'),
+        ('(', None) => Some(')'),
+        ('(', Some(next)) if next.is_whitespace() => Some(')'),
+        _ => None,
+    }
+}
+
+/// Read the rest of a comment, up to but not including the
+/// newline ending a line comment, or up to and including the
+/// `)` ending a block comment
+fn read_comment(
+    chars: &mut Peekable<impl Iterator<Item = (char, usize, usize, usize)>>,
+    comment: &mut Lexeme,
+    close: char
+) -> Result<(), LexError> {
+    loop {
+        let Some(&(ch, _, _, offset)) = chars.peek() else {
+            if close == '\n' {
+                return Ok(());
+            }
+            let (line, col) = (comment.line, comment.col);
+            return Err(LexError { message: "Unterminated comment".to_string(), line, col });
+        };
+        if ch == '\n' && close == '\n' {
+            return Ok(());
+        }
+        chars.next();
+        comment.text.push(ch);
+        comment.span.end = offset + ch.len_utf8();
+        if ch == close {
+            return Ok(());
+        }
+    }
+}
+
+/// Pair each character with its line, column and byte offset
+fn with_positions(fstr: &str) -> impl Iterator<Item = (char, usize, usize, usize)> + '_ {
+    let (mut line, mut col) = (1, 0);
+    fstr.char_indices().map(move |(offset, ch)| {
+        col += 1;
+        let pos = (ch, line, col, offset);
+        if ch == '\n' {
+            line += 1;
+            col = 0;
+        }
+        pos
+    })
+}
+
+/// Read the rest of a string whose opening quote (at `line`, `col`)
+/// has already been consumed, keeping the escape sequences as they
+/// were written so that `parse_string` can replace them. Gives the
+/// offset just past the closing quote
+fn read_string(
+    chars: &mut impl Iterator<Item = (char, usize, usize, usize)>,
+    current: &mut String,
+    line: usize,
+    col: usize
+) -> Result<usize, LexError> {
+    // the body of the string and where each of its chars came from
+    let mut body = String::new();
+    let mut positions = Vec::new();
+
+    let end = loop {
+        let Some((ch, ch_line, ch_col, offset)) = chars.next() else {
+            return Err(LexError { message: "Unterminated string".to_string(), line, col });
+        };
+        if ch == '"' {
+            break offset + 1;
+        }
+        body.push(ch);
+        positions.push((ch_line, ch_col));
+
+        // an escaped char never ends the string
+        if ch == '\\' {
+            if let Some((next, next_line, next_col, _)) = chars.next() {
+                body.push(next);
+                positions.push((next_line, next_col));
+            }
+        }
+    };
+
+    if let Err(idx) = unescape_string(&body) {
+        let (line, col) = positions[idx];
+        return Err(LexError { message: "Invalid escape sequence".to_string(), line, col });
+    }
+    current.push_str(&body);
+    current.push('"');
+    Ok(end)
+}
+
+/// Parse the lexeme as a value, as `parse_data_type`, giving
+/// the body of a lambda the positions it has in the source
+pub fn parse_lexeme(lexeme: &Lexeme) -> Option<Token> {
+    match parse_data_type(&lexeme.text)? {
+        Token::Lambda(mut lambda) => {
+            let bar = lexeme.text.find('|').expect("A lambda always has a bar!");
+            lambda.body = lambda.body.into_iter()
+                .map(|inner| lexeme.relocate(&lexeme.text[..=bar], inner))
+                .collect();
+            Some(Token::Lambda(lambda))
+        },
+        tok => Some(tok),
+    }
+}
+
+/// Read a `{ ... }` block into lexemes, positioned in the source
+pub fn read_block(block: &Lexeme) -> Option<Result<Vec<Lexeme>, LexError>> {
+    let inner = block.text.strip_prefix('{')?.strip_suffix('}')?;
+    Some(read_stack(inner).map(|lexemes| lexemes.into_iter()
+        .filter(|lexeme| !lexeme.is_trivia())
+        .map(|lexeme| block.relocate("{", lexeme))
+        .collect()))
+}
+
+pub fn parse_data_type(val: &str) -> Option<Token> {
     if let Ok(i) = val.parse::<i64>() {
         Some(Token::Int(i))
+    } else if let Ok(i) = val.parse::<BigInt>() {
+        Some(Token::BigInt(i))
+    } else if let Ok(i) = val.parse::<Rational>() {
+        Some(Token::from(i))
     } else if let Ok(i) = val.parse::<f64>() {
         Some(Token::Float(i))
     } else if let Ok(i) = parse_string(val) {
         Some(Token::Str(i))
     } else if let Ok(i) = parse_bool(val) {
         Some(Token::Bool(i))
+    } else if let Ok(i) = parse_bracketed(val) {
+        Some(i)
+    } else if let Ok(i) = parse_lambda(val) {
+        Some(Token::Lambda(i))
+    } else if let Some(word) = val.strip_prefix('\'').filter(|word| !word.is_empty()) {
+        // quoting a literal gives the literal itself, otherwise
+        // the word is kept as an operator to EVAL later
+        parse_data_type(word).or_else(|| Some(Token::Quoted(word.to_string())))
     } else {
         None
     }
@@ -0,0 +1,25 @@
+//! A stack based language interpreter. Embedders should only
+//! need the `Interpreter`, while the modules are exposed for
+//! the binary and for lower level use
+
+mod test;
+
+pub mod utils;
+pub mod bigint;
+pub mod lexer;
+pub mod stack;
+pub mod linalg;
+pub mod overflow;
+pub mod rational;
+pub mod error;
+pub mod registry;
+pub mod interpreter;
+pub mod runner;
+pub mod repl;
+pub mod cli;
+
+pub use error::{ExecError, StackError};
+pub use interpreter::Interpreter;
+pub use lexer::LexError;
+pub use overflow::OverflowPolicy;
+pub use utils::{Lambda, Token};
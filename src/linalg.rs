@@ -0,0 +1,131 @@
+use std::fmt;
+
+use crate::utils::Token;
+
+/// Raised when the shapes of the vectors or matrices
+/// given to an operator are not compatible
+#[derive(PartialEq, Clone, Debug)]
+pub enum DimensionError {
+    /// two vectors of different lengths, i.e. `(left, right)`
+    VectorLength(usize, usize),
+    /// the columns of the left matrix do not match the rows of
+    /// the right one, each given as `(rows, cols)`
+    MatrixProduct((usize, usize), (usize, usize)),
+    /// the columns of the matrix do not match the vector length
+    MatrixVector((usize, usize), usize),
+    /// the cross product is only defined for two 3-vectors
+    CrossProduct(usize, usize),
+}
+
+impl fmt::Display for DimensionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DimensionError::VectorLength(a, b) =>
+                write!(f, "Vector lengths do not match: {} and {}!", a, b),
+            DimensionError::MatrixProduct((ar, ac), (br, bc)) =>
+                write!(f, "Cannot multiply a {}x{} matrix by a {}x{} matrix!", ar, ac, br, bc),
+            DimensionError::MatrixVector((r, c), len) =>
+                write!(f, "Cannot multiply a {}x{} matrix by a vector of length {}!", r, c, len),
+            DimensionError::CrossProduct(a, b) =>
+                write!(f, "Cross product requires two 3-vectors, got lengths {} and {}!", a, b),
+        }
+    }
+}
+
+/// The `(rows, cols)` of a matrix, matrices are always
+/// rectangular so the first row gives the column count
+pub fn shape(matrix: &[Vec<Token>]) -> (usize, usize) {
+    (matrix.len(), matrix.first().map_or(0, |row| row.len()))
+}
+
+/// Apply the function to each pair of corresponding elements,
+/// the element operations may fail with any error that a
+/// `DimensionError` can be converted into
+pub fn elementwise<E: From<DimensionError>>(
+    first: Vec<Token>,
+    second: Vec<Token>,
+    func: impl Fn(Token, Token) -> Result<Token, E>
+) -> Result<Vec<Token>, E> {
+    if first.len() != second.len() {
+        return Err(DimensionError::VectorLength(first.len(), second.len()).into());
+    }
+    first.into_iter()
+        .zip(second)
+        .map(|(a, b)| func(a, b))
+        .collect()
+}
+
+/// The dot product of two vectors, i.e. the sum of
+/// the products of their corresponding elements
+pub fn dot<E: From<DimensionError>>(
+    first: Vec<Token>,
+    second: Vec<Token>,
+    add: impl Fn(Token, Token) -> Result<Token, E>,
+    mul: impl Fn(Token, Token) -> Result<Token, E>
+) -> Result<Token, E> {
+    let products = elementwise(first, second, mul)?;
+    products.into_iter().try_fold(Token::Int(0), add)
+}
+
+pub fn transpose(matrix: Vec<Vec<Token>>) -> Vec<Vec<Token>> {
+    let (_, cols) = shape(&matrix);
+    (0..cols)
+        .map(|j| matrix.iter().map(|row| row[j].clone()).collect())
+        .collect()
+}
+
+pub fn mat_mul<E: From<DimensionError>>(
+    first: Vec<Vec<Token>>,
+    second: Vec<Vec<Token>>,
+    add: impl Fn(Token, Token) -> Result<Token, E> + Copy,
+    mul: impl Fn(Token, Token) -> Result<Token, E> + Copy
+) -> Result<Vec<Vec<Token>>, E> {
+    let (fs, ss) = (shape(&first), shape(&second));
+    if fs.1 != ss.0 {
+        return Err(DimensionError::MatrixProduct(fs, ss).into());
+    }
+
+    let cols = transpose(second);
+    first.into_iter()
+        .map(|row| {
+            cols.iter()
+                .map(|col| dot(row.clone(), col.clone(), add, mul))
+                .collect()
+        })
+        .collect()
+}
+
+pub fn mat_vec_mul<E: From<DimensionError>>(
+    matrix: Vec<Vec<Token>>,
+    vector: Vec<Token>,
+    add: impl Fn(Token, Token) -> Result<Token, E> + Copy,
+    mul: impl Fn(Token, Token) -> Result<Token, E> + Copy
+) -> Result<Vec<Token>, E> {
+    let ms = shape(&matrix);
+    if ms.1 != vector.len() {
+        return Err(DimensionError::MatrixVector(ms, vector.len()).into());
+    }
+
+    matrix.into_iter()
+        .map(|row| dot(row, vector.clone(), add, mul))
+        .collect()
+}
+
+/// The cross product of two 3-vectors, i.e.
+/// `[a2b3 - a3b2, a3b1 - a1b3, a1b2 - a2b1]`
+pub fn cross<E: From<DimensionError>>(
+    first: Vec<Token>,
+    second: Vec<Token>,
+    sub: impl Fn(Token, Token) -> Result<Token, E>,
+    mul: impl Fn(Token, Token) -> Result<Token, E>
+) -> Result<Vec<Token>, E> {
+    if first.len() != 3 || second.len() != 3 {
+        return Err(DimensionError::CrossProduct(first.len(), second.len()).into());
+    }
+
+    let component = |i: usize, j: usize| sub(
+        mul(first[i].clone(), second[j].clone())?,
+        mul(first[j].clone(), second[i].clone())?
+    );
+    Ok(vec![component(1, 2)?, component(2, 0)?, component(0, 1)?])
+}
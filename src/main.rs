@@ -1,27 +1,73 @@
-mod test;
-
-mod utils;
-mod lexer;
-mod stack;
+use swen431_a1_rust::cli::{self, exit_code, Command};
+use swen431_a1_rust::repl;
+use swen431_a1_rust::runner::{self, exec, get_file_contents, render_stack_file};
 
 fn main() {
-    let args = std::env::args().collect::<Vec<String>>();
-
-    // if two args are provided, the second is a 
-    // 3 digit number that is the test case!
-    
-    match args.len() {
-        1 => (), // exec normally
-        2 => {   // file
-            let fnum = &args[1];
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+
+    let command = match cli::parse_args(&args) {
+        Ok(command) => command,
+        Err(msg) => {
+            eprintln!("{}\n\n{}", msg, cli::USAGE);
+            std::process::exit(cli::USAGE_ERROR)
+        },
+    };
+
+    let code = match command {
+        Command::Repl => match repl::Repl::new().run() {
+            Ok(()) => cli::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                cli::IO_ERROR
+            },
+        },
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            cli::SUCCESS
+        },
+        Command::Run(path) => run_script(&path),
+        Command::Case(fnum) => {
             evaluate_file_stack(&fnum);
-        }, 
-        _ => (),
-    }
+            cli::SUCCESS
+        },
+        Command::TestAll => match runner::run_all() {
+            true => cli::SUCCESS,
+            false => cli::RUNTIME_ERROR,
+        },
+        Command::Output(fnum) => write_output_file(&fnum),
+    };
+    std::process::exit(code)
+}
+
+/// Run a script from a file, or stdin for `-`, printing the final
+/// stack to stdout and any error to stderr. Gives the exit code
+fn run_script(path: &str) -> i32 {
+    let source = if path == "-" {
+        std::io::read_to_string(std::io::stdin())
+    } else {
+        std::fs::read_to_string(path)
+    };
+    let source = match source {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Could not read '{}': {}", path, e);
+            return cli::IO_ERROR;
+        },
+    };
 
+    match exec(&source) {
+        Ok(stack_format) => {
+            print!("{}", render_stack_file(&stack_format));
+            cli::SUCCESS
+        },
+        Err(e) => {
+            eprintln!("{}", e.render(&source));
+            exit_code(&e)
+        },
+    }
 }
 
-fn evaluate_file_stack(fnum: &String) {
+fn evaluate_file_stack(fnum: &str) {
     let input_path = format!("input/input-{}.txt", fnum);
     let expected_path = format!("expected/expected-{}.txt", fnum);
 
@@ -30,34 +76,47 @@ fn evaluate_file_stack(fnum: &String) {
     let (_, expected_stack_format) = get_file_contents(&expected_path);
 
     // execute the input file
-    let input_stack_format = exec(&input_content);
-
-    println!("Expected: {:?}\nGot: {:?}", expected_stack_format, input_stack_format);
-    println!("Result: {}", expected_stack_format == input_stack_format)
-}
-
-fn exec(stack_str: &String) -> Vec<String> {
-    let lexemes = lexer::read_stack(stack_str);
-    let res = stack::exec_stack(&lexemes);
-    
-    res.iter()
-        .map(|val| utils::extract_value_from_token::<String>(val.clone()))
-        .collect::<Vec<String>>()
-}
-
-fn get_file_contents(path: &str) -> (String, Vec<String>) {
-    let fstr = std::fs::read_to_string(path);
-    match fstr {
-        Ok(contents) => (
-            contents.clone(), 
-            contents.split("\r\n")
-                .filter(|val| !val.is_empty())
-                .map(|val| val.to_string())
-                .collect::<Vec<String>>()
-        ),
-        Err(_) => panic!("Could not read the file '{}'", path),
+    match exec(&input_content) {
+        Ok(input_stack_format) => {
+            println!("Expected: {:?}\nGot: {:?}", expected_stack_format, input_stack_format);
+            println!("Result: {}", expected_stack_format == input_stack_format)
+        },
+        Err(msg) => {
+            println!("Expected: {:?}\nError: {}", expected_stack_format, msg);
+            println!("Result: false")
+        },
     }
 }
 
+/// Run `input/input-NNN.txt` and write the final stack to
+/// `output/output-NNN.txt` in the same format as the expected 
+/// files. Gives the exit code
+fn write_output_file(fnum: &str) -> i32 {
+    let input_path = format!("input/input-{}.txt", fnum);
+    let output_path = format!("output/output-{}.txt", fnum);
 
+    let Ok(input_content) = std::fs::read_to_string(&input_path) else {
+        eprintln!("Could not read the file '{}'", input_path);
+        return cli::IO_ERROR;
+    };
+    let stack_format = match exec(&input_content) {
+        Ok(stack_format) => stack_format,
+        Err(e) => {
+            eprintln!("{}", e.render(&input_content));
+            return exit_code(&e);
+        },
+    };
 
+    let written = std::fs::create_dir_all("output")
+        .and_then(|_| std::fs::write(&output_path, render_stack_file(&stack_format)));
+    match written {
+        Ok(()) => {
+            println!("Wrote {}", output_path);
+            cli::SUCCESS
+        },
+        Err(e) => {
+            eprintln!("Could not write the file '{}': {}", output_path, e);
+            cli::IO_ERROR
+        },
+    }
+}
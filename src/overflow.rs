@@ -0,0 +1,89 @@
+use crate::bigint::BigInt;
+use crate::error::StackError;
+use crate::utils::Token;
+
+/// What the integer operators do with a result that does
+/// not fit in an i64
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum OverflowPolicy {
+    /// give the result as a big int instead
+    #[default]
+    BigInt,
+    /// stop with `StackError::Overflow`
+    Error,
+    /// wrap around, as two's complement
+    Wrap,
+    /// clamp to `i64::MIN` or `i64::MAX`
+    Saturate,
+    /// give the result as a float instead
+    Float,
+}
+
+impl OverflowPolicy {
+    /// Fit the exact result of an integer operation, which is
+    /// worked out as an i128 so that it cannot overflow itself
+    pub fn fit(self, exact: i128) -> Result<Token, StackError> {
+        if let Ok(val) = i64::try_from(exact) {
+            return Ok(Token::Int(val));
+        }
+        match self {
+            OverflowPolicy::BigInt => Ok(Token::BigInt(BigInt::from(exact))),
+            OverflowPolicy::Error => Err(StackError::Overflow),
+            OverflowPolicy::Wrap => Ok(Token::Int(exact as i64)),
+            OverflowPolicy::Saturate => Ok(Token::Int(if exact < 0 { i64::MIN } else { i64::MAX })),
+            OverflowPolicy::Float => Ok(Token::Float(exact as f64)),
+        }
+    }
+
+    /// Raise an int to a non-negative int power
+    pub fn pow(self, base: i64, exp: i64) -> Result<Token, StackError> {
+        let Ok(exp) = u64::try_from(exp) else {
+            return Err(StackError::BadArgument("Cannot raise an int to a negative power!".to_string()));
+        };
+
+        // any other base overflows an i128 well before the
+        // exponent gets too big for a u32
+        let exact = match base {
+            0 => Some(if exp == 0 { 1 } else { 0 }),
+            1 => Some(1),
+            -1 => Some(if exp % 2 == 0 { 1 } else { -1 }),
+            _ => u32::try_from(exp).ok().and_then(|exp| (base as i128).checked_pow(exp)),
+        };
+        if let Some(exact) = exact {
+            return self.fit(exact);
+        }
+
+        let negative = base < 0 && exp % 2 == 1;
+        match self {
+            OverflowPolicy::BigInt => BigInt::from(base).checked_pow(exp)
+                .map(Token::BigInt)
+                .ok_or_else(|| StackError::BadArgument("Cannot raise an int to a power this large!".to_string())),
+            OverflowPolicy::Error => Err(StackError::Overflow),
+            OverflowPolicy::Wrap => Ok(Token::Int(wrapping_pow(base, exp))),
+            OverflowPolicy::Saturate => Ok(Token::Int(if negative { i64::MIN } else { i64::MAX })),
+            OverflowPolicy::Float => Ok(Token::Float((base as f64).powf(exp as f64))),
+        }
+    }
+}
+
+/// `i64::wrapping_pow` for exponents too big for a u32
+fn wrapping_pow(mut base: i64, mut exp: u64) -> i64 {
+    let mut res: i64 = 1;
+    while exp > 0 {
+        if exp % 2 == 1 {
+            res = res.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exp /= 2;
+    }
+    res
+}
+
+/// Check that an int can be used to shift an i64 by, i.e.
+/// that it is in `0..=63`
+pub fn shift_count(count: i64) -> Result<u32, StackError> {
+    match u32::try_from(count) {
+        Ok(count) if count < i64::BITS => Ok(count),
+        _ => Err(StackError::BadArgument(format!("Cannot shift by {}, the count must be from 0 to 63!", count))),
+    }
+}
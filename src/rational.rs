@@ -0,0 +1,151 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+use crate::bigint::BigInt;
+
+/// An exact fraction, always kept in lowest terms with a
+/// positive denominator, i.e. `2/-4` is kept as `-1/2`
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Rational {
+    num: BigInt,
+    den: BigInt,
+}
+
+impl Rational {
+    /// The fraction `num/den`, or None if `den` is zero
+    pub fn new(num: BigInt, den: BigInt) -> Option<Rational> {
+        if den.is_zero() {
+            return None;
+        }
+        let gcd = num.gcd(&den);
+        let (num, den) = (
+            num.div_rem(&gcd).expect("Checked for zero!").0,
+            den.div_rem(&gcd).expect("Checked for zero!").0,
+        );
+        Some(if den.is_negative() { Rational { num: -num, den: -den } } else { Rational { num, den } })
+    }
+
+    pub fn numer(&self) -> &BigInt {
+        &self.num
+    }
+
+    pub fn denom(&self) -> &BigInt {
+        &self.den
+    }
+
+    /// Whether the denominator is 1, so that the value is
+    /// really an integer
+    pub fn is_integer(&self) -> bool {
+        self.den == BigInt::from(1i64)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.num.is_negative()
+    }
+
+    pub fn abs(&self) -> Rational {
+        Rational { num: self.num.abs(), den: self.den.clone() }
+    }
+
+    /// Flip the fraction over, or None if it is zero
+    pub fn recip(&self) -> Option<Rational> {
+        Rational::new(self.den.clone(), self.num.clone())
+    }
+
+    /// Round towards zero, as integer division does
+    pub fn trunc(&self) -> BigInt {
+        self.num.div_rem(&self.den).expect("The denominator is never zero!").0
+    }
+
+    /// The nearest float, which may be infinite or NaN if both
+    /// parts are too big for a float
+    pub fn to_f64(&self) -> f64 {
+        self.num.to_f64() / self.den.to_f64()
+    }
+
+    /// Divide, or None when dividing by zero
+    pub fn div(&self, other: &Rational) -> Option<Rational> {
+        Some(self * &other.recip()?)
+    }
+
+    /// Raise to a power, or None if either part could take up
+    /// more than `MAX_POW_BITS` bits
+    pub fn checked_pow(&self, exp: u64) -> Option<Rational> {
+        Some(Rational { num: self.num.checked_pow(exp)?, den: self.den.checked_pow(exp)? })
+    }
+}
+
+impl From<BigInt> for Rational {
+    fn from(num: BigInt) -> Rational {
+        Rational { num, den: BigInt::from(1i64) }
+    }
+}
+
+impl FromStr for Rational {
+    type Err = ();
+
+    /// Read a fraction written as `num/den`, where the
+    /// denominator is a non zero integer
+    fn from_str(val: &str) -> Result<Rational, ()> {
+        let (num, den) = val.split_once('/').ok_or(())?;
+        if den.starts_with(['-', '+']) {
+            return Err(());
+        }
+        Rational::new(num.parse()?, den.parse()?).ok_or(())
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        // the denominators are positive, so cross multiplying
+        // keeps the order
+        (&self.num * &other.den).cmp(&(&other.num * &self.den))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for Rational {
+    type Output = Rational;
+
+    fn neg(self) -> Rational {
+        Rational { num: -self.num, den: self.den }
+    }
+}
+
+impl Add for &Rational {
+    type Output = Rational;
+
+    fn add(self, other: &Rational) -> Rational {
+        let num = &(&self.num * &other.den) + &(&other.num * &self.den);
+        Rational::new(num, &self.den * &other.den).expect("The denominators are never zero!")
+    }
+}
+
+impl Sub for &Rational {
+    type Output = Rational;
+
+    fn sub(self, other: &Rational) -> Rational {
+        self + &-other.clone()
+    }
+}
+
+impl Mul for &Rational {
+    type Output = Rational;
+
+    fn mul(self, other: &Rational) -> Rational {
+        Rational::new(&self.num * &other.num, &self.den * &other.den).expect("The denominators are never zero!")
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::error::StackError;
+use crate::lexer;
+use crate::overflow::OverflowPolicy;
+use crate::utils::Token;
+
+/// The words handled by the executor itself rather than
+/// the registry, as they need the lambda being executed, the
+/// words defined by the script, or the lexemes that follow
+pub const CORE_WORDS: [&str; 16] = [
+    "EVAL", "SELF", "WORDS", ":", ";",
+    "IF", "ELSE", "THEN", "WHILE", "DO", "END", "LOOP", "TIMES", "I",
+    "STORE", "FETCH",
+];
+
+/// A function applying an operator to the stack
+pub type Handler = Rc<dyn Fn(&mut Vec<Token>) -> Result<(), StackError>>;
+
+/// How many values an operator pops and pushes, an operator
+/// such as ROLL may need more than `inputs` once it has
+/// looked at its arguments
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct StackEffect {
+    pub inputs: usize,
+    pub outputs: usize,
+}
+
+impl fmt::Display for StackEffect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "( {} -- {} )", self.inputs, self.outputs)
+    }
+}
+
+/// Raised when an operator cannot be registered
+#[derive(PartialEq, Clone, Debug)]
+pub enum RegistryError {
+    /// another operator was already registered under the name
+    AlreadyDefined(String),
+    /// the name would be read as a value, holds whitespace, or
+    /// is one of the core words or lambda parameters
+    InvalidName(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegistryError::AlreadyDefined(name) => write!(f, "The word '{}' is already defined!", name),
+            RegistryError::InvalidName(name) => write!(f, "Cannot use '{}' as the name of a word!", name),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+#[derive(Clone)]
+struct Operator {
+    effect: StackEffect,
+    handler: Handler,
+}
+
+/// The operators that can be applied by name, kept in name
+/// order so that they can be listed
+#[derive(Clone, Default)]
+pub struct Registry {
+    ops: BTreeMap<String, Operator>,
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.ops.keys()).finish()
+    }
+}
+
+impl Registry {
+    /// A registry without any operators
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// A registry holding the built-in operators
+    pub fn builtins() -> Registry {
+        let mut ops = Registry::new();
+        crate::stack::register_builtins(&mut ops, OverflowPolicy::default());
+        ops
+    }
+
+    /// Add an operator under the name, which may not already
+    /// be taken. The stack is checked to hold `inputs` values
+    /// before the handler is called
+    pub fn register(
+        &mut self,
+        name: &str,
+        inputs: usize,
+        outputs: usize,
+        handler: impl Fn(&mut Vec<Token>) -> Result<(), StackError> + 'static
+    ) -> Result<(), RegistryError> {
+        self.check_name(name)?;
+        let effect = StackEffect { inputs, outputs };
+        self.ops.insert(name.to_string(), Operator { effect, handler: Rc::new(handler) });
+        Ok(())
+    }
+
+    /// Add an operator under the name, replacing any operator
+    /// already registered under it
+    pub(crate) fn replace(
+        &mut self,
+        name: &str,
+        inputs: usize,
+        outputs: usize,
+        handler: impl Fn(&mut Vec<Token>) -> Result<(), StackError> + 'static
+    ) {
+        let effect = StackEffect { inputs, outputs };
+        self.ops.insert(name.to_string(), Operator { effect, handler: Rc::new(handler) });
+    }
+
+    /// Check that a new word could be given the name
+    pub fn check_name(&self, name: &str) -> Result<(), RegistryError> {
+        if !is_valid_name(name) {
+            return Err(RegistryError::InvalidName(name.to_string()));
+        }
+        if self.ops.contains_key(name) {
+            return Err(RegistryError::AlreadyDefined(name.to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.ops.contains_key(name)
+    }
+
+    /// Every registered word and its stack effect, by name
+    pub fn words(&self) -> impl Iterator<Item = (&str, StackEffect)> {
+        self.ops.iter().map(|(name, op)| (name.as_str(), op.effect))
+    }
+
+    /// Apply the named operator, giving None if there is no
+    /// operator of that name
+    pub fn call(&self, name: &str, stack: &mut Vec<Token>) -> Option<Result<(), StackError>> {
+        let op = self.ops.get(name)?;
+        if stack.len() < op.effect.inputs {
+            return Some(Err(StackError::Underflow { needed: op.effect.inputs, found: stack.len() }));
+        }
+        Some((op.handler)(stack))
+    }
+}
+
+/// Whether the lexer would hand the name to the executor as a
+/// word, without it being taken by the executor itself
+fn is_valid_name(name: &str) -> bool {
+    let is_param = name.strip_prefix('x')
+        .is_some_and(|idx| !idx.is_empty() && idx.chars().all(|ch| ch.is_ascii_digit()));
+
+    !name.is_empty()
+        && !name.starts_with(['\'', '"', '[', '{'])
+        && !name.starts_with("->")
+        && !name.contains(char::is_whitespace)
+        && !CORE_WORDS.contains(&name)
+        && !is_param
+        && lexer::parse_data_type(name).is_none()
+}
@@ -0,0 +1,114 @@
+use std::io::{self, BufRead, Write};
+
+use crate::interpreter::Interpreter;
+use crate::utils::Token;
+
+/// An interactive session that keeps its stack across lines
+#[derive(Default)]
+pub struct Repl {
+    interp: Interpreter,
+    /// the stack before each line that changed it, for `:undo`
+    history: Vec<Vec<Token>>,
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl::default()
+    }
+
+    /// Read lines from stdin until `:quit` or the end of input
+    pub fn run(&mut self) -> io::Result<()> {
+        let stdin = io::stdin();
+        let mut stdout = io::stdout();
+        let mut line = String::new();
+
+        loop {
+            write!(stdout, "> ")?;
+            stdout.flush()?;
+
+            line.clear();
+            if stdin.lock().read_line(&mut line)? == 0 {
+                writeln!(stdout)?;
+                return Ok(());
+            }
+            if !self.handle_line(&line, &mut stdout)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Handle a single line of input, either a meta-command or
+    /// stack code, writing the response to `out`. Gives false
+    /// once the session should end
+    pub fn handle_line(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let line = line.trim();
+        let (command, arg) = match line.split_once(char::is_whitespace) {
+            Some((command, arg)) => (command, arg.trim()),
+            None => (line, ""),
+        };
+
+        match command {
+            "" => (),
+            ":quit" | ":q" => return Ok(false),
+            ":stack" => self.print_stack(out)?,
+            ":words" => {
+                for (name, effect) in self.interp.words() {
+                    writeln!(out, "{} {}", name, effect)?;
+                }
+                for (name, body) in self.interp.definitions() {
+                    let body = body.iter().map(|lexeme| lexeme.text.as_str()).collect::<Vec<&str>>();
+                    writeln!(out, ": {} {} ;", name, body.join(" "))?;
+                }
+            }
+            ":clear" => {
+                self.history.push(self.interp.replace_stack(Vec::new()));
+                self.print_stack(out)?;
+            }
+            ":undo" => match self.history.pop() {
+                Some(prev) => {
+                    self.interp.replace_stack(prev);
+                    self.print_stack(out)?;
+                }
+                None => writeln!(out, "Nothing to undo")?,
+            },
+            ":load" => match std::fs::read_to_string(arg) {
+                Ok(contents) => self.exec(&contents, out)?,
+                Err(e) => writeln!(out, "Error: could not read the file '{}': {}", arg, e)?,
+            },
+            // a lone `:` starts a word definition rather than a command
+            _ if is_command(command) => writeln!(out, "Error: unknown command '{}'", command)?,
+            _ => self.exec(line, out)?,
+        }
+        Ok(true)
+    }
+
+    /// Execute the code on the current stack, which is left
+    /// as it was before if anything goes wrong
+    fn exec(&mut self, code: &str, out: &mut impl Write) -> io::Result<()> {
+        let before = self.interp.stack().to_vec();
+        match self.interp.eval(code) {
+            Ok(()) => {
+                self.history.push(before);
+                self.print_stack(out)
+            }
+            Err(e) => writeln!(out, "{}", e.render(code)),
+        }
+    }
+
+    /// One value per line with the top of the stack last
+    fn print_stack(&self, out: &mut impl Write) -> io::Result<()> {
+        if self.interp.stack().is_empty() {
+            return writeln!(out, "<empty>");
+        }
+        for val in self.interp.stack() {
+            writeln!(out, "{}", val)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the first word of a line is a meta-command, i.e. a
+/// `:` followed directly by a letter as in `:quit`
+fn is_command(word: &str) -> bool {
+    word.strip_prefix(':').is_some_and(|rest| rest.starts_with(|ch: char| ch.is_ascii_alphabetic()))
+}
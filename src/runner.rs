@@ -0,0 +1,189 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::error::ExecError;
+use crate::interpreter::Interpreter;
+
+/// The result of running one numbered input file against
+/// its expected file
+#[derive(PartialEq, Debug)]
+pub enum Outcome {
+    Pass,
+    Fail { expected: Vec<String>, actual: Vec<String> },
+    /// the case could not be run, i.e. a missing file, a lex or
+    /// stack error, or a panic in the interpreter
+    Error(String),
+}
+
+/// Find the number of every `input/input-NNN.txt`, in order
+pub fn discover_cases(input_dir: &str) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(input_dir) else {
+        return Vec::new();
+    };
+    let mut cases = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let fnum = name.strip_prefix("input-")?.strip_suffix(".txt")?;
+            Some(fnum.to_string())
+        })
+        .collect::<Vec<String>>();
+    cases.sort();
+    cases
+}
+
+/// Run a single case, isolating any panic so that one
+/// broken case cannot take down the rest of the run
+pub fn run_case(fnum: &str) -> Outcome {
+    let input_path = format!("input/input-{}.txt", fnum);
+    let expected_path = format!("expected/expected-{}.txt", fnum);
+
+    // the default hook would print every caught panic to stderr
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| ()));
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        let (input_content, _) = get_file_contents(&input_path);
+        let (_, expected) = get_file_contents(&expected_path);
+        exec(&input_content)
+            .map(|actual| (expected, actual))
+            .map_err(|e| e.to_string())
+    }));
+    panic::set_hook(hook);
+
+    match res {
+        Ok(Ok((expected, actual))) if expected == actual => Outcome::Pass,
+        Ok(Ok((expected, actual))) => Outcome::Fail { expected, actual },
+        Ok(Err(msg)) => Outcome::Error(msg),
+        Err(payload) => Outcome::Error(panic_message(payload)),
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
+
+/// Run every case that can be found, printing each failure
+/// and a summary. Gives whether every case passed
+pub fn run_all() -> bool {
+    let cases = discover_cases("input");
+    let mut failed = Vec::new();
+
+    for fnum in &cases {
+        match run_case(fnum) {
+            Outcome::Pass => continue,
+            Outcome::Fail { expected, actual } => {
+                println!("FAIL {}", fnum);
+                print!("{}", unified_diff(
+                    &expected,
+                    &actual,
+                    &format!("expected/expected-{}.txt", fnum),
+                    &format!("actual/input-{}.txt", fnum)
+                ));
+            }
+            Outcome::Error(msg) => println!("ERROR {}: {}", fnum, msg),
+        }
+        failed.push(fnum.clone());
+    }
+
+    println!(
+        "\n{} passed, {} failed, {} total",
+        cases.len() - failed.len(), failed.len(), cases.len()
+    );
+    if !failed.is_empty() {
+        println!("Failed: {}", failed.join(" "));
+    }
+    failed.is_empty()
+}
+
+/// A unified diff of two lists of lines as a single hunk
+/// with full context, i.e. `diff -U<n>` for small files
+pub fn unified_diff(expected: &[String], actual: &[String], from: &str, to: &str) -> String {
+    let mut res = format!("--- {}\n+++ {}\n", from, to);
+    res.push_str(&format!(
+        "@@ -{} +{} @@\n",
+        hunk_range(expected.len()), hunk_range(actual.len())
+    ));
+
+    // the longest common subsequence of lines, built from the end
+    // so that the diff can be read off from the start
+    let (n, m) = (expected.len(), actual.len());
+    let mut lcs = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            res.push_str(&format!(" {}\n", expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            res.push_str(&format!("-{}\n", expected[i]));
+            i += 1;
+        } else {
+            res.push_str(&format!("+{}\n", actual[j]));
+            j += 1;
+        }
+    }
+    res
+}
+
+fn hunk_range(len: usize) -> String {
+    match len {
+        0 => "0,0".to_string(),
+        len => format!("1,{}", len),
+    }
+}
+
+/// One value per line with the top of the stack last, 
+/// each line ending in a newline
+pub fn render_stack_file(stack_format: &[String]) -> String {
+    stack_format.iter()
+        .map(|val| format!("{}\n", val))
+        .collect()
+}
+
+/// Execute the stack, giving the final stack rendered 
+/// one element per string, or a description of the error
+pub fn exec(stack_str: &str) -> Result<Vec<String>, ExecError> {
+    let mut interp = Interpreter::new();
+    interp.eval(stack_str)?;
+
+    Ok(interp.stack().iter()
+        .map(|val| val.to_string())
+        .collect::<Vec<String>>())
+}
+
+pub fn get_file_contents(path: &str) -> (String, Vec<String>) {
+    let fstr = std::fs::read_to_string(path);
+    match fstr {
+        Ok(contents) => {
+            let lines = split_stack_lines(&contents);
+            (contents, lines)
+        },
+        Err(_) => panic!("Could not read the file '{}'", path),
+    }
+}
+
+/// Split a stack file into its values, one per line, whether the
+/// lines end in LF or CRLF. Trailing whitespace and blank lines
+/// are ignored, so an empty file is an empty stack
+pub fn split_stack_lines(contents: &str) -> Vec<String> {
+    contents.lines()
+        .map(|val| val.trim_end())
+        .filter(|val| !val.is_empty())
+        .map(|val| val.to_string())
+        .collect::<Vec<String>>()
+}
@@ -1,88 +1,631 @@
 // use crate::extract_value_from_token;
-use crate::lexer;
-use crate::utils::{most_generic_type, extract_value_from_token, Token};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::rc::Rc;
 
+use crate::error::StackError;
+use crate::lexer::{self, Lexeme};
+use crate::bigint::BigInt;
+use crate::linalg;
+use crate::overflow::{self, OverflowPolicy};
+use crate::rational::Rational;
+use crate::registry::Registry;
+use crate::utils::{self, most_generic_type, extract_value_from_token, Lambda, Token};
 
-/// Given a list of strings, sequentially iterate 
+
+/// Given a list of lexemes, sequentially iterate 
 /// the list, adding data to the stack and applying 
 /// operators when they are encountered
 /// 
 /// ```
+/// use swen431_a1_rust::{lexer, stack, Token};
+///
 /// let stack_str = "1 2 DUP + +";
-/// let lexemes = lexer::read_stack(&stack_str);
-/// let res = stack::exec_stack(&lexemes);
+/// let lexemes = lexer::read_stack(stack_str).unwrap();
+/// let res = stack::exec_stack(&lexemes).unwrap();
 /// assert_eq!(Token::Int(5), res[0])
 /// ```
-pub fn exec_stack(lexemes: &Vec<String>) -> Vec<Token> {
+pub fn exec_stack(lexemes: &[Lexeme]) -> Result<Vec<Token>, StackError> {
+    exec_stack_with_depth(lexemes, DEFAULT_MAX_DEPTH)
+}
+
+/// The deepest lambdas may call each other before 
+/// execution is stopped
+pub const DEFAULT_MAX_DEPTH: usize = 500;
+
+/// As `exec_stack`, but with a limit on how deeply lambdas 
+/// may recurse, so that runaway recursion is reported
+/// rather than overflowing the Rust stack
+pub fn exec_stack_with_depth(lexemes: &[Lexeme], max_depth: usize) -> Result<Vec<Token>, StackError> {
     let mut stack = Vec::new();
+    exec_on_stack(&mut stack, lexemes, &mut Env::default(), max_depth)?;
+    Ok(stack)
+}
+
+/// Everything kept between executions besides the stack
+#[derive(Clone, Debug)]
+pub struct Env {
+    /// the operators that can be applied by name
+    pub ops: Registry,
+    /// the words defined by `: NAME ... ;`, as their bodies
+    pub words: BTreeMap<String, Rc<[Lexeme]>>,
+    /// the variables stored at the top level
+    pub vars: BTreeMap<String, Token>,
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Env { ops: Registry::builtins(), words: BTreeMap::new(), vars: BTreeMap::new() }
+    }
+}
+
+/// Execute the lexemes on top of an existing stack, i.e. to
+/// carry a stack across lines. On error the stack is left as
+/// it was when the error occurred
+pub fn exec_on_stack(
+    stack: &mut Vec<Token>,
+    lexemes: &[Lexeme],
+    env: &mut Env,
+    max_depth: usize
+) -> Result<(), StackError> {
+    // comments are kept by the lexer but never executed
+    let code = lexemes.iter()
+        .filter(|lexeme| !lexeme.is_trivia())
+        .cloned()
+        .collect::<Vec<Lexeme>>();
+    let mut root = Frame {
+        args: Vec::new(),
+        lambda: None,
+        locals: HashMap::new(),
+        indices: Vec::new(),
+        depth: 0,
+        max_depth
+    };
+    exec_lexemes(stack, &code, &mut root, env)
+}
+
+/// The lambda or word that is currently executing along 
+/// with the arguments bound to it, empty at the top level
+struct Frame {
+    args: Vec<Token>,
+    lambda: Option<Lambda>,
+    /// the variables first stored within this lambda or word
+    locals: HashMap<String, Token>,
+    /// the index of each enclosing DO loop, innermost last
+    indices: Vec<i64>,
+    depth: usize,
+    max_depth: usize,
+}
 
-    for val in lexemes {
-        if let Some(tok) = lexer::parse_data_type(val) {
-            stack.push(tok);
-            continue;
+impl Frame {
+    /// Resolve a positional parameter `xN` to its argument
+    fn arg(&self, val: &str) -> Option<Token> {
+        let idx = val.strip_prefix('x')?.parse::<usize>().ok()?;
+        self.args.get(idx).cloned()
+    }
+
+    /// The frame for a lambda or word called from this one
+    fn call(&self, args: Vec<Token>, lambda: Option<Lambda>) -> Result<Frame, StackError> {
+        if self.depth >= self.max_depth {
+            return Err(StackError::DepthExceeded(self.max_depth));
         }
-        perform_token_operation(&mut stack, val);
+        Ok(Frame {
+            args,
+            lambda,
+            locals: HashMap::new(),
+            indices: Vec::new(),
+            depth: self.depth + 1,
+            max_depth: self.max_depth
+        })
     }
-    stack
 }
 
-fn perform_token_operation(mut stack: &mut Vec<Token>, val: &String) {
-    match val.as_str() {
-        "+" => apply_bin_op(&mut stack, add),
-        "-" => apply_bin_op(&mut stack, sub),
-        "*" => apply_bin_op(&mut stack, mul),
-        "/" => apply_bin_op(&mut stack, div),
-        "**" => apply_bin_op(&mut stack, exp),
-        "%" => apply_bin_op(&mut stack, modu),
+fn exec_lexemes(stack: &mut Vec<Token>, lexemes: &[Lexeme], frame: &mut Frame, env: &mut Env) -> Result<(), StackError> {
+    exec_range(stack, lexemes, 0..lexemes.len(), frame, env)
+}
 
-        "<<" => apply_bin_op(&mut stack, bitl),
-        ">>" => apply_bin_op(&mut stack, bitr),
+/// Execute part of the lexemes, i.e. the branch of an IF, so
+/// that errors are still located within the whole list
+fn exec_range(
+    stack: &mut Vec<Token>,
+    lexemes: &[Lexeme],
+    range: Range<usize>,
+    frame: &mut Frame,
+    env: &mut Env
+) -> Result<(), StackError> {
+    let mut i = range.start;
+    while i < range.end {
+        let lexeme = &lexemes[i];
+        let val = &lexeme.text;
+        let res = match val.as_str() {
+            ":" => define_word(&lexemes[i + 1..range.end], frame, env).map(|len| i += len),
+            // each gives the position of the lexeme ending it
+            "IF" => exec_if(stack, lexemes, i, range.end, frame, env).map(|end| i = end),
+            "WHILE" => exec_while(stack, lexemes, i, range.end, frame, env).map(|end| i = end),
+            "DO" => exec_do(stack, lexemes, i, range.end, frame, env).map(|end| i = end),
+            "TIMES" => exec_times(stack, lexemes, i, range.end, frame, env).map(|end| i = end),
+            _ => exec_lexeme(stack, lexeme, frame, env),
+        };
+        res.map_err(|e| e.at(lexeme))?;
+        i += 1;
+    }
+    Ok(())
+}
+
+/// Push the value the lexeme holds, or apply it as an operator
+#[inline(never)]
+fn exec_lexeme(stack: &mut Vec<Token>, lexeme: &Lexeme, frame: &mut Frame, env: &mut Env) -> Result<(), StackError> {
+    match lexer::parse_lexeme(lexeme) {
+        // a lambda literal is applied as soon as it is reached,
+        // quoting it with ' pushes it as a value instead
+        Some(Token::Lambda(lambda)) if !lexeme.text.starts_with('\'') => call_lambda(stack, &lambda, frame, env),
+        Some(tok) => push(stack, tok),
+        None => perform_token_operation(stack, &lexeme.text, frame, env),
+    }
+}
 
-        "==" => apply_bin_op(&mut stack, equequ),
-        "!=" => apply_bin_op(&mut stack, notequ),
-        ">" => apply_bin_op(&mut stack, gt),
-        "<" => apply_bin_op(&mut stack, lt),
-        ">=" => apply_bin_op(&mut stack, ge),
-        "<=" => apply_bin_op(&mut stack, le),
+/// Pop the arguments of the lambda off the stack and execute
+/// its body against the remaining stack, one level deeper 
+/// than the caller
+fn call_lambda(stack: &mut Vec<Token>, lambda: &Lambda, caller: &Frame, env: &mut Env) -> Result<(), StackError> {
+    require(stack, lambda.arity)?;
+    let mut frame = caller.call(Vec::new(), Some(lambda.clone()))?;
+    frame.args = stack.split_off(stack.len() - lambda.arity);
+    exec_lexemes(stack, &lambda.body, &mut frame, env)
+}
 
-        "&" => apply_bin_op(&mut stack, and),
-        "|" => apply_bin_op(&mut stack, or),
-        "^" => apply_bin_op(&mut stack, xor),
+/// Execute the body of a word one level deeper than the
+/// caller, without the caller's arguments
+fn call_word(stack: &mut Vec<Token>, body: &[Lexeme], caller: &Frame, env: &mut Env) -> Result<(), StackError> {
+    let mut frame = caller.call(Vec::new(), None)?;
+    exec_lexemes(stack, body, &mut frame, env)
+}
 
-        "DROP" => drop(&mut stack),
-        "DUP" => dup(&mut stack),
-        "SWAP" => swap(&mut stack),
-        "ROT" => rot(&mut stack, 3),
-        "ROLL" => rol(&mut stack),
-        "ROLLD" => rold(&mut stack),
-        "IFELSE" => ifelse(&mut stack),
+/// Read the `NAME ... ;` following a `:` into a word, giving 
+/// how many lexemes it took up. Words may be redefined, and
+/// as they are looked up when called they may be recursive
+fn define_word(rest: &[Lexeme], frame: &Frame, env: &mut Env) -> Result<usize, StackError> {
+    let bad = |msg: &str| Err(StackError::Syntax(msg.to_string()));
 
-        _ => panic!("Unsupported stack element: '{}'", val),
+    let Some(end) = rest.iter().position(|lexeme| lexeme.text == ";") else {
+        return bad("Missing ';' to end the definition!");
+    };
+    let Some((name, body)) = rest[..end].split_first() else {
+        return bad("Missing a name after ':'!");
+    };
+    if body.iter().any(|lexeme| lexeme.text == ":") {
+        return bad("Cannot define a word inside another definition!");
+    }
+    env.ops.check_name(&name.text).map_err(|e| StackError::Syntax(e.to_string()))?;
+    if fetch(&name.text, frame, env).is_some() {
+        return Err(StackError::Syntax(format!("The variable '{}' is already defined!", name.text)));
     }
+
+    env.words.insert(name.text.clone(), body.into());
+    Ok(end + 1)
 }
 
-///
-/// VOID OPERATORS
+fn perform_token_operation(stack: &mut Vec<Token>, val: &str, frame: &mut Frame, env: &mut Env) -> Result<(), StackError> {
+    if val == "EVAL" {
+        eval(stack, frame, env)
+    } else if let Some(res) = env.ops.call(val, stack) {
+        res
+    } else if let Some(body) = env.words.get(val).cloned() {
+        call_word(stack, &body, frame, env)
+    } else {
+        perform_core_word(stack, val, frame, env)
+    }
+}
+
+/// The words that are neither operators nor defined by the
+/// script
+#[inline(never)]
+fn perform_core_word(stack: &mut Vec<Token>, val: &str, frame: &mut Frame, env: &mut Env) -> Result<(), StackError> {
+    match val {
+        "SELF" => match &frame.lambda {
+            Some(lambda) => push(stack, Token::Lambda(lambda.clone())),
+            None => Err(StackError::BadArgument("Cannot use SELF outside of a lambda!".to_string())),
+        },
+        "WORDS" => {
+            let names = env.words.keys().map(|name| Token::Str(name.clone()));
+            push(stack, Token::Vector(names.collect()))
+        },
+        ";" => Err(StackError::Syntax("Found ';' outside of a definition!".to_string())),
+        "I" => match frame.indices.last() {
+            Some(idx) => push(stack, Token::Int(*idx)),
+            None => Err(StackError::BadArgument("Cannot use I outside of a DO loop!".to_string())),
+        },
+        "ELSE" | "THEN" => unmatched(val, "IF"),
+        "END" => unmatched(val, "WHILE"),
+        "LOOP" => unmatched(val, "DO"),
+        "IF" | "WHILE" | "DO" | "TIMES" =>
+            Err(StackError::Syntax(format!("Cannot apply '{}' on its own!", val))),
+
+        "STORE" => {
+            require(stack, 2)?;
+            let name = pop_var_name(stack)?;
+            let val = stack.pop().expect("Unreachable!");
+            store(&name, val, frame, env)
+        },
+        "FETCH" => {
+            require(stack, 1)?;
+            let name = pop_var_name(stack)?;
+            match fetch(&name, frame, env) {
+                Some(val) => push(stack, val),
+                None => Err(StackError::UndefinedVariable(name)),
+            }
+        },
+        _ if val.len() > 2 && val.starts_with("->") => {
+            require(stack, 1)?;
+            let top = stack.pop().expect("Unreachable!");
+            store(&val[2..], top, frame, env)
+        },
+
+        _ => if let Some(arg) = frame.arg(val) {
+            push(stack, arg)
+        } else if let Some(var) = fetch(val, frame, env) {
+            push(stack, var)
+        } else if let Some(msg) = utils::matrix_error(val) {
+            Err(StackError::Syntax(msg))
+        } else {
+            Err(StackError::UnknownWord(val.to_string()))
+        },
+    }
+}
+
+//
+// VARIABLES
+//
+
+fn pop_var_name(stack: &mut Vec<Token>) -> Result<String, StackError> {
+    match stack.pop().expect("Unreachable!") {
+        Token::Quoted(name) => Ok(name),
+        tok => Err(StackError::TypeMismatch(format!("Expected a quoted variable name but found {}!", tok))),
+    }
+}
+
+/// Bind the value to the name, updating the local or global
+/// variable if there is one. Otherwise a new variable is local
+/// to the lambda or word being executed, or global at the top
+/// level
+fn store(name: &str, val: Token, frame: &mut Frame, env: &mut Env) -> Result<(), StackError> {
+    if env.words.contains_key(name) {
+        return Err(StackError::BadArgument(format!("The word '{}' is already defined!", name)));
+    }
+    env.ops.check_name(name).map_err(|e| StackError::BadArgument(e.to_string()))?;
+
+    if let Some(local) = frame.locals.get_mut(name) {
+        *local = val;
+    } else if frame.depth == 0 || env.vars.contains_key(name) {
+        env.vars.insert(name.to_string(), val);
+    } else {
+        frame.locals.insert(name.to_string(), val);
+    }
+    Ok(())
+}
+
+/// The value of a local variable, else that of the global 
+/// variable with the name
+fn fetch(name: &str, frame: &Frame, env: &Env) -> Option<Token> {
+    frame.locals.get(name)
+        .or_else(|| env.vars.get(name))
+        .cloned()
+}
+
+//
+// CONTROL FLOW
+//
+
+fn unmatched(val: &str, opener: &str) -> Result<(), StackError> {
+    Err(StackError::Syntax(format!("Found '{}' without a matching {}!", val, opener)))
+}
+
+/// Find the first of the targets from `start`, skipping over
+/// any control structures nested in between
+fn find_closing(lexemes: &[Lexeme], start: usize, end: usize, targets: &[&str]) -> Option<usize> {
+    let mut i = start;
+    while i < end {
+        let val = lexemes[i].text.as_str();
+        if targets.contains(&val) {
+            return Some(i);
+        }
+        i = match val {
+            "IF" => find_closing(lexemes, i + 1, end, &["THEN"])?,
+            "WHILE" => {
+                let body = find_while_do(lexemes, i + 1, end)?;
+                find_closing(lexemes, body + 1, end, &["END"])?
+            },
+            "DO" => find_closing(lexemes, i + 1, end, &["LOOP"])?,
+            _ => i,
+        } + 1;
+    }
+    None
+}
+
+/// Find the `DO` ending the condition of a WHILE, skipping over
+/// any counted `DO ... LOOP` in the condition, which starts with
+/// the same word
+fn find_while_do(lexemes: &[Lexeme], start: usize, end: usize) -> Option<usize> {
+    let mut i = start;
+    loop {
+        let body = find_closing(lexemes, i, end, &["DO"])?;
+        match find_closing(lexemes, body + 1, end, &["LOOP", "END"]) {
+            Some(stop) if lexemes[stop].text == "LOOP" => i = stop + 1,
+            _ => return Some(body),
+        }
+    }
+}
+
+fn missing(msg: &str) -> StackError {
+    StackError::Syntax(msg.to_string())
+}
+
+/// Run the body of a control structure in the current frame.
+/// Each nested body counts toward the depth limit as a call
+/// would, since it uses up the Rust stack just as a call does.
+/// For the limit to be enough, every function on the path of a
+/// recursive call must keep its frame small, so the helpers
+/// with large locals are marked `#[inline(never)]` to keep them
+/// off that path
+fn exec_nested(
+    stack: &mut Vec<Token>,
+    lexemes: &[Lexeme],
+    range: Range<usize>,
+    frame: &mut Frame,
+    env: &mut Env
+) -> Result<(), StackError> {
+    if frame.depth >= frame.max_depth {
+        return Err(StackError::DepthExceeded(frame.max_depth));
+    }
+    frame.depth += 1;
+    let res = exec_range(stack, lexemes, range, frame, env);
+    frame.depth -= 1;
+    res
+}
+
+/// Pop a bool and run only the branch it selects
+/// 
+/// `cond IF ... THEN` or `cond IF ... ELSE ... THEN`
+#[inline(never)]
+fn exec_if(
+    stack: &mut Vec<Token>,
+    lexemes: &[Lexeme],
+    at: usize,
+    end: usize,
+    frame: &mut Frame,
+    env: &mut Env
+) -> Result<usize, StackError> {
+    let mid = find_closing(lexemes, at + 1, end, &["ELSE", "THEN"])
+        .ok_or_else(|| missing("Missing THEN to close IF!"))?;
+    let then = match lexemes[mid].text.as_str() {
+        "ELSE" => find_closing(lexemes, mid + 1, end, &["THEN"])
+            .ok_or_else(|| missing("Missing THEN to close IF!"))?,
+        _ => mid,
+    };
+
+    require(stack, 1)?;
+    let cond = extract_value_from_token::<bool>(stack.pop().expect("Unreachable!"))?;
+    let branch = match cond {
+        true => at + 1..mid,
+        false => (mid + 1).min(then)..then,
+    };
+    exec_nested(stack, lexemes, branch, frame, env)?;
+    Ok(then)
+}
+
+/// Run the condition, then the body for as long as the
+/// condition leaves true on the stack
+/// 
+/// `WHILE ... DO ... END`
+#[inline(never)]
+fn exec_while(
+    stack: &mut Vec<Token>,
+    lexemes: &[Lexeme],
+    at: usize,
+    end: usize,
+    frame: &mut Frame,
+    env: &mut Env
+) -> Result<usize, StackError> {
+    let body = find_while_do(lexemes, at + 1, end)
+        .ok_or_else(|| missing("Missing DO after WHILE!"))?;
+    let stop = find_closing(lexemes, body + 1, end, &["END"])
+        .ok_or_else(|| missing("Missing END to close WHILE!"))?;
+
+    loop {
+        exec_nested(stack, lexemes, at + 1..body, frame, env)?;
+        require(stack, 1)?;
+        if !extract_value_from_token::<bool>(stack.pop().expect("Unreachable!"))? {
+            return Ok(stop);
+        }
+        exec_nested(stack, lexemes, body + 1..stop, frame, env)?;
+    }
+}
+
+/// Pop the start index and then the limit, running the body
+/// once for each index from the start up to but not including
+/// the limit. `I` pushes the index of the innermost loop
+/// 
+/// `limit start DO ... LOOP`
+#[inline(never)]
+fn exec_do(
+    stack: &mut Vec<Token>,
+    lexemes: &[Lexeme],
+    at: usize,
+    end: usize,
+    frame: &mut Frame,
+    env: &mut Env
+) -> Result<usize, StackError> {
+    let stop = find_closing(lexemes, at + 1, end, &["LOOP"])
+        .ok_or_else(|| missing("Missing LOOP to close DO!"))?;
+
+    require(stack, 2)?;
+    let start = extract_value_from_token::<i64>(stack.pop().expect("Unreachable!"))?;
+    let limit = extract_value_from_token::<i64>(stack.pop().expect("Unreachable!"))?;
+    for idx in start..limit {
+        frame.indices.push(idx);
+        let res = exec_nested(stack, lexemes, at + 1..stop, frame, env);
+        frame.indices.pop();
+        res?;
+    }
+    Ok(stop)
+}
+
+/// Pop a count and run the block or lambda following TIMES
+/// that many times
 /// 
+/// `n TIMES { ... }`
+#[inline(never)]
+fn exec_times(
+    stack: &mut Vec<Token>,
+    lexemes: &[Lexeme],
+    at: usize,
+    end: usize,
+    frame: &mut Frame,
+    env: &mut Env
+) -> Result<usize, StackError> {
+    let (count, repeated) = times_args(stack, lexemes, at, end)?;
+    match repeated {
+        Repeated::Lambda(lambda) => for _ in 0..count {
+            call_lambda(stack, &lambda, frame, env)?;
+        },
+        // the block runs in the current frame, so it can still
+        // see the arguments and loop indices
+        Repeated::Block(body) => for _ in 0..count {
+            exec_nested(stack, &body, 0..body.len(), frame, env)?;
+        },
+    }
+    Ok(at + 1)
+}
+
+/// What TIMES runs, either a lambda literal or a plain block
+enum Repeated {
+    Lambda(Lambda),
+    Block(Vec<Lexeme>),
+}
+
+/// Pop the count for TIMES and read the block following it
+#[inline(never)]
+fn times_args(stack: &mut Vec<Token>, lexemes: &[Lexeme], at: usize, end: usize) -> Result<(i64, Repeated), StackError> {
+    let block = lexemes[..end].get(at + 1)
+        .filter(|block| block.text.starts_with('{') && block.text.ends_with('}'))
+        .ok_or_else(|| missing("TIMES must be followed by a { ... } block!"))?;
+
+    require(stack, 1)?;
+    let count = extract_value_from_token::<i64>(stack.pop().expect("Unreachable!"))?;
+    if count < 0 {
+        return Err(StackError::BadArgument("Cannot invoke TIMES with a negative count!".to_string()));
+    }
+
+    let repeated = match lexer::parse_lexeme(block) {
+        Some(Token::Lambda(lambda)) => Repeated::Lambda(lambda),
+        _ => Repeated::Block(lexer::read_block(block)
+            .expect("Checked to be a block above!")
+            .map_err(|e| StackError::Syntax(e.to_string()))?),
+    };
+    Ok((count, repeated))
+}
+
+type BinaryFn = fn(Token, Token) -> Result<Token, StackError>;
+type UnaryFn = fn(Token) -> Result<Token, StackError>;
+type CheckedFn = fn(Token, Token, OverflowPolicy) -> Result<Token, StackError>;
+type CheckedUnaryFn = fn(Token, OverflowPolicy) -> Result<Token, StackError>;
+type StackFn = fn(&mut Vec<Token>) -> Result<(), StackError>;
+
+/// Add the built-in operators to the registry, each with
+/// the number of values it pops and pushes
+pub fn register_builtins(ops: &mut Registry, overflow: OverflowPolicy) {
+    register_arithmetic(ops, overflow);
+    let binary: [(&str, BinaryFn); 12] = [
+        ("RDIV", exact_div), (">>", bitr),
+        ("==", equequ), ("!=", notequ), (">", gt), ("<", lt), (">=", ge), ("<=", le), ("<=>", spaceship),
+        ("&", and), ("|", or), ("^", xor),
+    ];
+    let unary: [(&str, UnaryFn); 3] = [("~", bitnot), ("!", not), ("TRANSP", transp)];
+    // the minimum each pops and pushes, ROLL and ROLLD also
+    // move as many values as the count they pop
+    let shuffles: [(&str, usize, usize, StackFn); 7] = [
+        ("DROP", 1, 0, drop),
+        ("DUP", 1, 2, dup),
+        ("SWAP", 2, 2, |stack| swap(stack)),
+        ("ROT", 3, 3, |stack| rot(stack, 3)),
+        ("ROLL", 1, 0, rol),
+        ("ROLLD", 1, 0, rold),
+        ("IFELSE", 3, 1, ifelse),
+    ];
+
+    let res = binary.into_iter()
+        .try_for_each(|(name, func)| ops.register(name, 2, 1, move |stack| apply_bin_op(stack, func)))
+        .and_then(|_| unary.into_iter()
+            .try_for_each(|(name, func)| ops.register(name, 1, 1, move |stack| apply_unary_op(stack, func))))
+        .and_then(|_| shuffles.into_iter()
+            .try_for_each(|(name, inputs, outputs, func)| ops.register(name, inputs, outputs, func)));
+    res.expect("The built-in operators should not collide!")
+}
+
+/// Add the arithmetic operators, which follow the overflow
+/// policy, replacing any that were added under another policy
+pub fn register_arithmetic(ops: &mut Registry, overflow: OverflowPolicy) {
+    let binary: [(&str, CheckedFn); 8] = [
+        ("+", add), ("-", sub), ("*", mul), ("/", div), ("**", exp), ("%", modu), ("<<", bitl), ("x", cross),
+    ];
+    let unary: [(&str, CheckedUnaryFn); 2] = [("NEG", neg), ("ABS", abs)];
+
+    for (name, func) in binary {
+        ops.replace(name, 2, 1, move |stack| apply_bin_op(stack, |a, b| func(a, b, overflow)));
+    }
+    for (name, func) in unary {
+        ops.replace(name, 1, 1, move |stack| apply_unary_op(stack, |top| func(top, overflow)));
+    }
+}
+
+/// Check that the stack holds at least the number of
+/// elements an operator needs
+fn require(stack: &[Token], needed: usize) -> Result<(), StackError> {
+    if stack.len() < needed {
+        return Err(StackError::Underflow { needed, found: stack.len() });
+    }
+    Ok(())
+}
+
+fn push(stack: &mut Vec<Token>, tok: Token) -> Result<(), StackError> {
+    stack.push(tok);
+    Ok(())
+}
+
+/// Shorthand for an operator rejecting the types it was given
+fn mismatch(msg: &str) -> Result<Token, StackError> {
+    Err(StackError::TypeMismatch(msg.to_string()))
+}
+
+//
+// VOID OPERATORS
+//
 
-pub fn drop(stack: &mut Vec<Token>) {
-    assert!(stack.len() >= 1, "Cannot DROP nothing!");
+pub fn drop(stack: &mut Vec<Token>) -> Result<(), StackError> {
+    require(stack, 1)?;
     stack.pop().expect("Unreachable error!");
+    Ok(())
 }
 
-pub fn dup(stack: &mut Vec<Token>) {
-    assert!(stack.len() >= 1, "Cannot DUP nothing!");
-    stack.push(stack[stack.len() - 1].clone())
+pub fn dup(stack: &mut Vec<Token>) -> Result<(), StackError> {
+    require(stack, 1)?;
+    stack.push(stack[stack.len() - 1].clone());
+    Ok(())
 }
 
-pub fn swap(stack: &mut Vec<Token>) {
-    assert!(stack.len() >= 2, "Cannot SWAP without at least 2 elements!");
+pub fn swap(stack: &mut [Token]) -> Result<(), StackError> {
+    require(stack, 2)?;
     let top = stack.len() - 1;
-    stack.swap(top, top - 1)
+    stack.swap(top, top - 1);
+    Ok(())
 }
 
-pub fn rot(stack: &mut Vec<Token>, num_to_rot: usize) {
-    assert!(stack.len() >= num_to_rot, "Cannot ROT without at least 3 elements!");
+pub fn rot(stack: &mut [Token], num_to_rot: usize) -> Result<(), StackError> {
+    require(stack, num_to_rot)?;
+    if num_to_rot == 0 {
+        return Ok(());
+    }
     let len = stack.len();
     let last = len - num_to_rot;
     let last_tok = stack[last].clone();
@@ -91,45 +634,128 @@ pub fn rot(stack: &mut Vec<Token>, num_to_rot: usize) {
         stack[i] = stack[i + 1].clone()
     }
     stack[len - 1] = last_tok;
+    Ok(())
 }
 
-pub fn rol(stack: &mut Vec<Token>) {
-    assert!(stack.len() >= 4, "Cannot ROLL without at least 4 elements!");
-    let rot_count = extract_value_from_token::<i64>(stack.pop().expect("Unreachable!"));
-    assert!(rot_count >= 0, "Cannot invoke ROLL with a neagtive arg!");
+/// Pop the count off the top of the stack for ROLL and ROLLD
+fn pop_roll_count(stack: &mut Vec<Token>) -> Result<usize, StackError> {
+    require(stack, 1)?;
+    let rot_count = extract_value_from_token::<i64>(stack.pop().expect("Unreachable!"))?;
+    if rot_count < 0 {
+        return Err(StackError::BadArgument("Cannot invoke ROLL or ROLLD with a negative arg!".to_string()));
+    }
+    Ok(rot_count as usize)
+}
 
-    rot(stack, rot_count as usize)
+pub fn rol(stack: &mut Vec<Token>) -> Result<(), StackError> {
+    let rot_count = pop_roll_count(stack)?;
+    rot(stack, rot_count)
 }
 
-pub fn rold(stack: &mut Vec<Token>) {
-    assert!(stack.len() >= 4, "Cannot ROT without at least 4 elements!");
-    let rot_count = extract_value_from_token::<i64>(stack.pop().expect("Unreachable!"));
-    assert!(rot_count >= 0, "Cannot invoke ROT with a neagtive arg!");
+pub fn rold(stack: &mut Vec<Token>) -> Result<(), StackError> {
+    let rot_count = pop_roll_count(stack)?;
+    require(stack, rot_count)?;
+    if rot_count == 0 {
+        return Ok(());
+    }
     
     let len = stack.len();
     let last = len - 1;
     let last_tok = stack[last].clone();
 
-    for i in (len + 1 - rot_count as usize..len).rev() {
+    for i in (len + 1 - rot_count..len).rev() {
         stack[i] = stack[i - 1].clone()
     }
-    stack[len - rot_count as usize] = last_tok;
+    stack[len - rot_count] = last_tok;
+    Ok(())
 }
 
-pub fn ifelse(stack: &mut Vec<Token>) {
-    let op = stack.pop().expect("");
-    let val = extract_value_from_token::<bool>(op);
+pub fn ifelse(stack: &mut Vec<Token>) -> Result<(), StackError> {
+    require(stack, 3)?;
+    let op = stack.pop().expect("Unreachable!");
+    let val = extract_value_from_token::<bool>(op)?;
     if val {
         stack.remove(stack.len() - 1);
     } else {
         stack.remove(stack.len() - 2);
     }
+    Ok(())
 }
 
 
-///
-/// BINARY OPERATIONS
-///
+/// Pop the top of the stack and apply it if it is a quoted
+/// operator or a lambda, any other value evaluates to itself
+fn eval(stack: &mut Vec<Token>, frame: &mut Frame, env: &mut Env) -> Result<(), StackError> {
+    require(stack, 1)?;
+    match stack.pop().expect("Unreachable!") {
+        Token::Quoted(op) => perform_token_operation(stack, &op, frame, env),
+        Token::Lambda(lambda) => call_lambda(stack, &lambda, frame, env),
+        val => push(stack, val),
+    }
+}
+
+//
+// UNARY OPERATIONS
+//
+
+/// Function that pops the top element off the stack and
+/// pushes the result of the unary function applied to it
+pub fn apply_unary_op(
+    stack: &mut Vec<Token>, 
+    func: impl Fn(Token) -> Result<Token, StackError>
+) -> Result<(), StackError> {
+    require(stack, 1)?;
+    let top = stack.pop().expect("Unreachable err.");
+
+    let res = func(top)?;
+    stack.push(res);
+    Ok(())
+}
+
+pub fn bitnot(top: Token) -> Result<Token, StackError> {
+    match top {
+        Token::Int(val) => Ok(Token::Int(!val)),
+        Token::BigInt(val) => Ok(Token::from(!&val)),
+        _ => mismatch("Cannot perform ~ on non ints!"),
+    }
+}
+
+pub fn not(top: Token) -> Result<Token, StackError> {
+    match top {
+        Token::Bool(val) => Ok(Token::Bool(!val)),
+        _ => mismatch("Cannot perform ! on non bools!"),
+    }
+}
+
+pub fn neg(top: Token, overflow: OverflowPolicy) -> Result<Token, StackError> {
+    match top {
+        Token::Int(val) => overflow.fit(-(val as i128)),
+        Token::BigInt(val) => Ok(Token::from(-val)),
+        Token::Rational(val) => Ok(Token::Rational(Box::new(-*val))),
+        Token::Float(val) => Ok(Token::Float(-val)),
+        _ => mismatch("Cannot perform NEG on non numeric types!"),
+    }
+}
+
+pub fn abs(top: Token, overflow: OverflowPolicy) -> Result<Token, StackError> {
+    match top {
+        Token::Int(val) => overflow.fit((val as i128).abs()),
+        Token::BigInt(val) => Ok(Token::from(val.abs())),
+        Token::Rational(val) => Ok(Token::Rational(Box::new(val.abs()))),
+        Token::Float(val) => Ok(Token::Float(val.abs())),
+        _ => mismatch("Cannot perform ABS on non numeric types!"),
+    }
+}
+
+pub fn transp(top: Token) -> Result<Token, StackError> {
+    let matrix = extract_value_from_token::<Vec<Vec<Token>>>(top)?;
+    Ok(Token::Matrix(linalg::transpose(matrix)))
+}
+
+
+//
+// BINARY OPERATIONS
+//
 
 
 /// 
@@ -139,144 +765,411 @@ pub fn ifelse(stack: &mut Vec<Token>) {
 /// and passes them to the binary function passed as an argument! 
 pub fn apply_bin_op(
     stack: &mut Vec<Token>, 
-    func: impl Fn(Token, Token) -> Token
-) {
-    assert!(stack.len() >= 2, "Cannot + without at least 2 elements!");
+    func: impl Fn(Token, Token) -> Result<Token, StackError>
+) -> Result<(), StackError> {
+    require(stack, 2)?;
     let second = stack.pop().expect("Unreachable err.");
     let first = stack.pop().expect("Unreachable err.");
    
-    let res = func(first, second);
-    stack.push(res.clone())
+    let res = func(first, second)?;
+    stack.push(res);
+    Ok(())
 }
 
-fn bin_int_function(first: Token, second: Token, func: impl Fn(i64, i64) -> i64) -> Token {
+fn bin_int_function(first: Token, second: Token, func: impl Fn(i64, i64) -> i64) -> Result<Token, StackError> {
     let (fv, sv) = (
-        extract_value_from_token::<i64>(first), 
-        extract_value_from_token::<i64>(second)
+        extract_value_from_token::<i64>(first)?, 
+        extract_value_from_token::<i64>(second)?
     );
     let res = func(fv, sv);
-    Token::Int(res)
+    Ok(Token::Int(res))
 }
 
-fn bin_float_function(first: Token, second: Token, func: impl Fn(f64, f64) -> f64) -> Token {
+/// As `bin_int_function`, but worked out exactly and then
+/// fitted back into an i64 by the overflow policy
+fn bin_checked_function(
+    first: Token, 
+    second: Token, 
+    overflow: OverflowPolicy,
+    func: impl Fn(i128, i128) -> i128
+) -> Result<Token, StackError> {
+    let (fv, sv) = (
+        extract_value_from_token::<i64>(first)?, 
+        extract_value_from_token::<i64>(second)?
+    );
+    overflow.fit(func(fv.into(), sv.into()))
+}
+
+/// Apply the function to two ints, either of which may be
+/// big, giving back an Int if the result fits in one
+fn bin_big_function(first: Token, second: Token, func: impl Fn(&BigInt, &BigInt) -> BigInt) -> Result<Token, StackError> {
     let (fv, sv) = (
-        extract_value_from_token::<f64>(first), 
-        extract_value_from_token::<f64>(second)
+        extract_value_from_token::<BigInt>(first)?, 
+        extract_value_from_token::<BigInt>(second)?
+    );
+    Ok(Token::from(func(&fv, &sv)))
+}
+
+/// Apply the function to two exact numbers, either of which
+/// may be a rational, giving back an integer if the result is
+/// whole. The function gives None on division by zero
+fn bin_rational_function(
+    first: Token, 
+    second: Token, 
+    func: impl Fn(&Rational, &Rational) -> Option<Rational>
+) -> Result<Token, StackError> {
+    let (fv, sv) = (
+        extract_value_from_token::<Rational>(first)?, 
+        extract_value_from_token::<Rational>(second)?
+    );
+    func(&fv, &sv).map(Token::from).ok_or(StackError::DivisionByZero)
+}
+
+/// As `bin_int_function`, rejecting shift counts an i64
+/// cannot be shifted by
+fn bin_shift_function(
+    first: Token, 
+    second: Token, 
+    func: impl Fn(i64, u32) -> Result<Token, StackError>
+) -> Result<Token, StackError> {
+    let (fv, sv) = (
+        extract_value_from_token::<i64>(first)?, 
+        extract_value_from_token::<i64>(second)?
+    );
+    func(fv, overflow::shift_count(sv)?)
+}
+
+/// As `bin_shift_function`, for shifting a big int, where
+/// the count must still be an int
+fn bin_big_shift_function(first: Token, second: Token, func: impl Fn(&BigInt, u32) -> BigInt) -> Result<Token, StackError> {
+    let (fv, sv) = (
+        extract_value_from_token::<BigInt>(first)?, 
+        extract_value_from_token::<i64>(second)?
+    );
+    Ok(Token::from(func(&fv, overflow::shift_count(sv)?)))
+}
+
+fn bin_float_function(first: Token, second: Token, func: impl Fn(f64, f64) -> f64) -> Result<Token, StackError> {
+    let (fv, sv) = (
+        extract_value_from_token::<f64>(first)?, 
+        extract_value_from_token::<f64>(second)?
     );
     let res = func(fv, sv);
-    Token::Float(res)
+    Ok(Token::Float(res))
 }
 
-fn bin_string_function(first: Token, second: Token, func: impl Fn(String, String) -> String) -> Token {
+fn bin_string_function(first: Token, second: Token, func: impl Fn(String, String) -> String) -> Result<Token, StackError> {
     let (fv, sv) = (
-        extract_value_from_token::<String>(first), 
-        extract_value_from_token::<String>(second)
+        extract_value_from_token::<String>(first)?, 
+        extract_value_from_token::<String>(second)?
     );
     let res = func(fv, sv);
-    Token::Str(res)
+    Ok(Token::Str(res))
 }
 
 fn bin_bool_function<T>(
     first: Token, 
     second: Token, 
     func: impl Fn(T, T) -> bool
-) -> Token 
+) -> Result<Token, StackError> 
 where 
-    T: From<Token>
+    T: TryFrom<Token, Error = StackError>
 {
     let (fv, sv) = (
-        extract_value_from_token::<T>(first),
-        extract_value_from_token::<T>(second)
+        extract_value_from_token::<T>(first)?,
+        extract_value_from_token::<T>(second)?
     );
     let res = func(fv, sv);
-    Token::Bool(res)
+    Ok(Token::Bool(res))
 }
 
-/// OPERATOR implementations
+fn bin_vector_function(
+    first: Token, 
+    second: Token, 
+    func: impl Fn(Token, Token) -> Result<Token, StackError>
+) -> Result<Token, StackError> {
+    let (fv, sv) = (
+        extract_value_from_token::<Vec<Token>>(first)?, 
+        extract_value_from_token::<Vec<Token>>(second)?
+    );
+    let res = linalg::elementwise(fv, sv, func)?;
+    Ok(Token::Vector(res))
+}
+
+fn bin_matrix_function(
+    first: Token, 
+    second: Token, 
+    func: impl Fn(Token, Token) -> Result<Token, StackError>
+) -> Result<Token, StackError> {
+    let (fv, sv) = (
+        extract_value_from_token::<Vec<Vec<Token>>>(first)?, 
+        extract_value_from_token::<Vec<Vec<Token>>>(second)?
+    );
+    let (fs, ss) = (linalg::shape(&fv), linalg::shape(&sv));
+    if fs != ss {
+        return Err(StackError::BadArgument(
+            format!("Matrix shapes do not match: {}x{} and {}x{}!", fs.0, fs.1, ss.0, ss.1)
+        ));
+    }
 
-pub fn add(first: Token, second: Token) -> Token {
+    // the shapes match, so each pair of rows can be combined as vectors
+    let rows = fv.into_iter().map(Token::Vector).collect();
+    let other_rows = sv.into_iter().map(Token::Vector).collect();
+    let res = linalg::elementwise(rows, other_rows, |a, b| bin_vector_function(a, b, &func))?;
+    let res = res.into_iter()
+        .map(extract_value_from_token::<Vec<Token>>)
+        .collect::<Result<Vec<Vec<Token>>, StackError>>()?;
+    Ok(Token::Matrix(res))
+}
+
+/// Multiply where at least one side is a matrix, dispatching on
+/// the shapes of the operands
+fn matrix_product(first: Token, second: Token, overflow: OverflowPolicy) -> Result<Token, StackError> {
+    let add = move |a, b| add(a, b, overflow);
+    let mul = move |a, b| mul(a, b, overflow);
+    match (first, second) {
+        (Token::Matrix(a), Token::Matrix(b)) => linalg::mat_mul(a, b, add, mul).map(Token::Matrix),
+        (Token::Matrix(a), Token::Vector(v)) => linalg::mat_vec_mul(a, v, add, mul).map(Token::Vector),
+        (Token::Vector(v), Token::Matrix(a)) => {
+            linalg::mat_vec_mul(linalg::transpose(a), v, add, mul).map(Token::Vector)
+        },
+        (first, second) => Err(StackError::TypeMismatch(
+            format!("Cannot multiply {} by {}!", first, second)
+        )),
+    }
+}
+
+/// Whether the token is a numeric zero, i.e. a divisor to reject
+fn is_zero(tok: &Token) -> bool {
+    matches!(tok, Token::Int(0)) || matches!(tok, Token::Float(val) if *val == 0.0)
+}
+
+fn bin_cmp_function<T>(
+    first: Token, 
+    second: Token, 
+    func: impl Fn(T, T) -> Ordering
+) -> Result<Token, StackError> 
+where 
+    T: TryFrom<Token, Error = StackError>
+{
+    let (fv, sv) = (
+        extract_value_from_token::<T>(first)?,
+        extract_value_from_token::<T>(second)?
+    );
+    let res = func(fv, sv);
+    Ok(Token::Int(res as i64))
+}
+
+// OPERATOR implementations
+
+pub fn add(first: Token, second: Token, overflow: OverflowPolicy) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
-        Token::Int(_) => bin_int_function(first, second, |a, b| a + b),
+        Token::Int(_) => bin_checked_function(first, second, overflow, |a, b| a + b),
+        Token::BigInt(_) => bin_big_function(first, second, |a, b| a + b),
+        Token::Rational(_) => bin_rational_function(first, second, |a, b| Some(a + b)),
         Token::Float(_) => bin_float_function(first, second, |a, b| a + b),
         Token::Str(_) => bin_string_function(first, second, |mut a, b| { a.push_str(b.as_str()); a }),
-        Token::Bool(_) => panic!("Cannot add booleans!"),
+        Token::Bool(_) => mismatch("Cannot add booleans!"),
+        Token::Quoted(_) => mismatch("Cannot add quoted operators!"),
+        Token::Lambda(_) => mismatch("Cannot add lambdas!"),
+        Token::Vector(_) => bin_vector_function(first, second, |a, b| add(a, b, overflow)),
+        Token::Matrix(_) => bin_matrix_function(first, second, |a, b| add(a, b, overflow)),
     }
 }
 
-pub fn sub(first: Token, second: Token) -> Token {
+pub fn sub(first: Token, second: Token, overflow: OverflowPolicy) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
-        Token::Int(_) => bin_int_function(first, second, |a, b| a - b),
+        Token::Int(_) => bin_checked_function(first, second, overflow, |a, b| a - b),
+        Token::BigInt(_) => bin_big_function(first, second, |a, b| a - b),
+        Token::Rational(_) => bin_rational_function(first, second, |a, b| Some(a - b)),
         Token::Float(_) => bin_float_function(first, second, |a, b| a - b),
-        Token::Str(_) => panic!("Cannot perform subtraction on Strings!"),
-        Token::Bool(_) => panic!("Cannot subtract booleans!"),
+        Token::Str(_) => mismatch("Cannot perform subtraction on Strings!"),
+        Token::Bool(_) => mismatch("Cannot subtract booleans!"),
+        Token::Quoted(_) => mismatch("Cannot subtract quoted operators!"),
+        Token::Lambda(_) => mismatch("Cannot subtract lambdas!"),
+        Token::Vector(_) => bin_vector_function(first, second, |a, b| sub(a, b, overflow)),
+        Token::Matrix(_) => bin_matrix_function(first, second, |a, b| sub(a, b, overflow)),
     }
 }
 
-pub fn mul(first: Token, second: Token) -> Token {
+/// The longest string `*` may build, as repeating a string could
+/// otherwise be asked for more memory than there is
+pub const MAX_STRING_LEN: usize = 1 << 24;
+
+pub fn mul(first: Token, second: Token, overflow: OverflowPolicy) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
-        Token::Int(_) => bin_int_function(first, second, |a, b| a * b),
+        Token::Int(_) => bin_checked_function(first, second, overflow, |a, b| a * b),
+        Token::BigInt(_) => bin_big_function(first, second, |a, b| a * b),
+        Token::Rational(_) => bin_rational_function(first, second, |a, b| Some(a * b)),
         Token::Float(_) => bin_float_function(first, second, |a, b| a * b),
         Token::Str(fv) => {
-            let sv = extract_value_from_token::<i64>(second);
+            let sv = extract_value_from_token::<i64>(second)?;
             if sv < 0 {
-                panic!("Cannot multiply Strings by negative int!")
+                return Err(StackError::BadArgument("Cannot multiply Strings by negative int!".to_string()));
             }
-            return Token::Str(fv.repeat(sv as usize))
+            usize::try_from(sv).ok()
+                .filter(|&sv| fv.len().checked_mul(sv).is_some_and(|len| len <= MAX_STRING_LEN))
+                .map(|sv| Token::Str(fv.repeat(sv)))
+                .ok_or_else(|| StackError::BadArgument("Cannot repeat a string this many times!".to_string()))
+        },
+        Token::Bool(_) => mismatch("Cannot multiply booleans!"),
+        Token::Quoted(_) => mismatch("Cannot multiply quoted operators!"),
+        Token::Lambda(_) => mismatch("Cannot multiply lambdas!"),
+        Token::Vector(_) => {
+            let (fv, sv) = (
+                extract_value_from_token::<Vec<Token>>(first)?, 
+                extract_value_from_token::<Vec<Token>>(second)?
+            );
+            linalg::dot(fv, sv, |a, b| add(a, b, overflow), |a, b| mul(a, b, overflow))
         },
-        Token::Bool(_) => panic!("Cannot multiply booleans!"),
+        Token::Matrix(_) => matrix_product(first, second, overflow),
     }
 }
 
-pub fn div(first: Token, second: Token) -> Token {
+pub fn div(first: Token, second: Token, overflow: OverflowPolicy) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
-        Token::Int(_) => bin_int_function(first, second, |a, b| {
-            if b != 0 { 
-                a / b 
-            } else { 
-                panic!("Cannot divide by 0!") 
-            }
-        }),
-        Token::Float(_) => bin_float_function(first, second, |a, b| {
-            if b != 0.0 { 
-                a / b 
-            } else { 
-                panic!("Cannot divide by 0!") 
-            }
-        }),
-        Token::Str(_) => panic!("Cannot perform division on Strings!"),
-        Token::Bool(_) => panic!("Cannot divide booleans!"),
+        Token::Int(_) | Token::BigInt(_) | Token::Rational(_) | Token::Float(_) if is_zero(&second) => {
+            Err(StackError::DivisionByZero)
+        },
+        Token::Int(_) => bin_checked_function(first, second, overflow, |a, b| a / b),
+        Token::BigInt(_) => bin_big_function(first, second, |a, b| a.div_rem(b).expect("Checked for zero!").0),
+        Token::Rational(_) => bin_rational_function(first, second, Rational::div),
+        Token::Float(_) => bin_float_function(first, second, |a, b| a / b),
+        Token::Str(_) => mismatch("Cannot perform division on Strings!"),
+        Token::Bool(_) => mismatch("Cannot divide booleans!"),
+        Token::Quoted(_) => mismatch("Cannot divide quoted operators!"),
+        Token::Lambda(_) => mismatch("Cannot divide lambdas!"),
+        Token::Vector(_) | Token::Matrix(_) => mismatch("Cannot divide vectors or matrices!"),
+    }
+}
+
+/// Divide without losing anything, so ints that do not divide
+/// exactly give a rational, i.e. `1 3 RDIV` gives `1/3`
+pub fn exact_div(first: Token, second: Token) -> Result<Token, StackError> {
+    match most_generic_type(&first, &second) {
+        Token::Int(_) | Token::BigInt(_) | Token::Rational(_) | Token::Float(_) if is_zero(&second) => {
+            Err(StackError::DivisionByZero)
+        },
+        Token::Int(_) | Token::BigInt(_) | Token::Rational(_) => bin_rational_function(first, second, Rational::div),
+        Token::Float(_) => bin_float_function(first, second, |a, b| a / b),
+        Token::Str(_) => mismatch("Cannot perform division on Strings!"),
+        Token::Bool(_) => mismatch("Cannot divide booleans!"),
+        Token::Quoted(_) => mismatch("Cannot divide quoted operators!"),
+        Token::Lambda(_) => mismatch("Cannot divide lambdas!"),
+        Token::Vector(_) | Token::Matrix(_) => mismatch("Cannot divide vectors or matrices!"),
     }
 }
 
-pub fn exp(first: Token, second: Token) -> Token {
+pub fn exp(first: Token, second: Token, overflow: OverflowPolicy) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
-        Token::Int(_) => bin_int_function(first, second, |a, b| a.pow(b as u32)),
+        Token::Int(_) => {
+            let (fv, sv) = (
+                extract_value_from_token::<i64>(first)?, 
+                extract_value_from_token::<i64>(second)?
+            );
+            overflow.pow(fv, sv)
+        },
+        Token::BigInt(_) => {
+            let (fv, sv) = (
+                extract_value_from_token::<BigInt>(first)?, 
+                extract_value_from_token::<BigInt>(second)?
+            );
+            if sv.is_negative() {
+                return Err(StackError::BadArgument("Cannot raise an int to a negative power!".to_string()));
+            }
+            sv.to_i64()
+                .and_then(|sv| fv.checked_pow(sv as u64))
+                .map(Token::from)
+                .ok_or_else(|| StackError::BadArgument("Cannot raise an int to a power this large!".to_string()))
+        },
+        Token::Rational(_) if matches!(second, Token::Int(_) | Token::BigInt(_)) => {
+            let (fv, sv) = (
+                extract_value_from_token::<Rational>(first)?, 
+                extract_value_from_token::<BigInt>(second)?
+            );
+            let res = sv.abs().to_i64()
+                .and_then(|exp| fv.checked_pow(exp as u64))
+                .ok_or_else(|| StackError::BadArgument("Cannot raise a rational to a power this large!".to_string()))?;
+            match sv.is_negative() {
+                true => res.recip().map(Token::from).ok_or(StackError::DivisionByZero),
+                false => Ok(Token::from(res)),
+            }
+        },
+        // a fractional power is rarely rational, so give a float
+        Token::Rational(_) => bin_float_function(first, second, |a, b| a.powf(b)),
         Token::Float(_) => bin_float_function(first, second, |a, b| a.powf(b)),
-        Token::Str(_) => panic!("Cannot perform expontiation on Strings!"),
-        Token::Bool(_) => panic!("Cannot perform exponentiation on  booleans!"),
+        Token::Str(_) => mismatch("Cannot perform expontiation on Strings!"),
+        Token::Bool(_) => mismatch("Cannot perform exponentiation on  booleans!"),
+        Token::Quoted(_) => mismatch("Cannot perform exponentiation on quoted operators!"),
+        Token::Lambda(_) => mismatch("Cannot perform exponentiation on lambdas!"),
+        Token::Vector(_) | Token::Matrix(_) => mismatch("Cannot perform exponentiation on vectors or matrices!"),
     }
 }
 
-pub fn modu(first: Token, second: Token) -> Token {
+pub fn modu(first: Token, second: Token, overflow: OverflowPolicy) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
-        Token::Int(_) => bin_int_function(first, second, |a, b| a % b),
+        Token::Int(_) | Token::BigInt(_) | Token::Rational(_) | Token::Float(_) if is_zero(&second) => {
+            Err(StackError::DivisionByZero)
+        },
+        Token::Int(_) => bin_checked_function(first, second, overflow, |a, b| a % b),
+        Token::BigInt(_) => bin_big_function(first, second, |a, b| a.div_rem(b).expect("Checked for zero!").1),
+        // takes the sign of the first, as the remainder of ints does
+        Token::Rational(_) => bin_rational_function(first, second, |a, b| {
+            let quot = Rational::from(a.div(b)?.trunc());
+            Some(a - &(b * &quot))
+        }),
         Token::Float(_) => bin_float_function(first, second, |a, b| a % b),
-        Token::Str(_) => panic!("Cannot perform modular operation on Strings!"),
-        Token::Bool(_) => panic!("Cannot perform modular operation on booleans!"),
+        Token::Str(_) => mismatch("Cannot perform modular operation on Strings!"),
+        Token::Bool(_) => mismatch("Cannot perform modular operation on booleans!"),
+        Token::Quoted(_) => mismatch("Cannot perform modular operation on quoted operators!"),
+        Token::Lambda(_) => mismatch("Cannot perform modular operation on lambdas!"),
+        Token::Vector(_) | Token::Matrix(_) => mismatch("Cannot perform modular operation on vectors or matrices!"),
     }
 }
 
-pub fn bitl(first: Token, second: Token) -> Token {
+/// Shift left, which is multiplying by a power of 2, so bits
+/// shifted out of an i64 are an overflow
+pub fn bitl(first: Token, second: Token, overflow: OverflowPolicy) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
-        Token::Int(_) => bin_int_function(first, second, |a, b| a << b),
-        _ => panic!("Cannot perform bitwse operation on non ints!"),
+        Token::Int(_) => bin_shift_function(first, second, |a, b| overflow.fit(i128::from(a) << b)),
+        Token::BigInt(_) => bin_big_shift_function(first, second, |a, b| a << b),
+        _ => mismatch("Cannot perform bitwse operation on non ints!"),
     }
 }
 
-pub fn bitr(first: Token, second: Token) -> Token {
+pub fn bitr(first: Token, second: Token) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
-        Token::Int(_) => bin_int_function(first, second, |a, b| a >> b),
-        _ => panic!("Cannot perform bitwse operation on non ints!"),
+        Token::Int(_) => bin_shift_function(first, second, |a, b| Ok(Token::Int(a >> b))),
+        Token::BigInt(_) => bin_big_shift_function(first, second, |a, b| a >> b),
+        _ => mismatch("Cannot perform bitwse operation on non ints!"),
+    }
+}
+
+pub fn cross(first: Token, second: Token, overflow: OverflowPolicy) -> Result<Token, StackError> {
+    match most_generic_type(&first, &second) {
+        Token::Vector(_) => {
+            let (fv, sv) = (
+                extract_value_from_token::<Vec<Token>>(first)?, 
+                extract_value_from_token::<Vec<Token>>(second)?
+            );
+            // promote every element to the most generic element type 
+            // so that the result is consistent, i.e. all floats
+            let generic = fv.iter()
+                .chain(&sv)
+                .fold(Token::Int(0), |acc, tok| most_generic_type(&acc, tok));
+            let promote = match generic {
+                Token::Int(_) | Token::BigInt(_) | Token::Rational(_) => |tok: Token| Ok(tok),
+                Token::Float(_) => |tok: Token| extract_value_from_token::<f64>(tok).map(Token::Float),
+                _ => return mismatch("Cannot perform cross product on non numeric vectors!"),
+            };
+            let (fv, sv) = (
+                fv.into_iter().map(promote).collect::<Result<Vec<Token>, StackError>>()?, 
+                sv.into_iter().map(promote).collect::<Result<Vec<Token>, StackError>>()?
+            );
+            let res = linalg::cross(fv, sv, |a, b| sub(a, b, overflow), |a, b| mul(a, b, overflow))?;
+            Ok(Token::Vector(res))
+        },
+        _ => mismatch("Cannot perform cross product on non vector types!"),
     }
 }
 
@@ -284,74 +1177,109 @@ pub fn bitr(first: Token, second: Token) -> Token {
 /// 
 /// BOOLEAN operators
 /// 
-pub fn equequ(first: Token, second: Token) -> Token {
+pub fn equequ(first: Token, second: Token) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
         Token::Int(_) => bin_bool_function::<i64>(first, second, |a, b| a == b),
+        Token::BigInt(_) => bin_bool_function::<BigInt>(first, second, |a, b| a == b),
+        Token::Rational(_) => bin_bool_function::<Rational>(first, second, |a, b| a == b),
         Token::Float(_) => bin_bool_function::<f64>(first, second, |a, b| a == b),
         Token::Str(_) => bin_bool_function::<String>(first, second, |a, b| a == b),
         Token::Bool(_) => bin_bool_function::<bool>(first, second, |a, b| a == b),
+        Token::Vector(_) | Token::Matrix(_) | Token::Quoted(_) | Token::Lambda(_) => {
+            Ok(Token::Bool(first == second))
+        },
     }
 }
 
-pub fn notequ(first: Token, second: Token) -> Token {
+pub fn notequ(first: Token, second: Token) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
         Token::Int(_) => bin_bool_function::<i64>(first, second, |a, b| a != b),
+        Token::BigInt(_) => bin_bool_function::<BigInt>(first, second, |a, b| a != b),
+        Token::Rational(_) => bin_bool_function::<Rational>(first, second, |a, b| a != b),
         Token::Float(_) => bin_bool_function::<f64>(first, second, |a, b| a != b),
         Token::Str(_) => bin_bool_function::<String>(first, second, |a, b| a != b),
         Token::Bool(_) => bin_bool_function::<bool>(first, second, |a, b| a != b),
+        Token::Vector(_) | Token::Matrix(_) | Token::Quoted(_) | Token::Lambda(_) => {
+            Ok(Token::Bool(first != second))
+        },
     }
 }
 
-pub fn gt(first: Token, second: Token) -> Token {
+pub fn gt(first: Token, second: Token) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
         Token::Int(_) => bin_bool_function::<i64>(first, second, |a, b| a > b),
+        Token::BigInt(_) => bin_bool_function::<BigInt>(first, second, |a, b| a > b),
+        Token::Rational(_) => bin_bool_function::<Rational>(first, second, |a, b| a > b),
         Token::Float(_) => bin_bool_function::<f64>(first, second, |a, b| a > b),
-        _ => panic!("Cannot perform > on non numeric types!")
+        _ => mismatch("Cannot perform > on non numeric types!")
     }
 }
 
-pub fn lt(first: Token, second: Token) -> Token {
+pub fn lt(first: Token, second: Token) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
         Token::Int(_) => bin_bool_function::<i64>(first, second, |a, b| a < b),
+        Token::BigInt(_) => bin_bool_function::<BigInt>(first, second, |a, b| a < b),
+        Token::Rational(_) => bin_bool_function::<Rational>(first, second, |a, b| a < b),
         Token::Float(_) => bin_bool_function::<f64>(first, second, |a, b| a < b),
-        _ => panic!("Cannot perform < on non numeric types!")
+        _ => mismatch("Cannot perform < on non numeric types!")
     }
 }
 
-pub fn ge(first: Token, second: Token) -> Token {
+pub fn ge(first: Token, second: Token) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
         Token::Int(_) => bin_bool_function::<i64>(first, second, |a, b| a >= b),
+        Token::BigInt(_) => bin_bool_function::<BigInt>(first, second, |a, b| a >= b),
+        Token::Rational(_) => bin_bool_function::<Rational>(first, second, |a, b| a >= b),
         Token::Float(_) => bin_bool_function::<f64>(first, second, |a, b| a >= b),
-        _ => panic!("Cannot perform >= on non numeric types!")
+        _ => mismatch("Cannot perform >= on non numeric types!")
     }
 }
 
-pub fn le(first: Token, second: Token) -> Token {
+pub fn le(first: Token, second: Token) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
         Token::Int(_) => bin_bool_function::<i64>(first, second, |a, b| a <= b),
+        Token::BigInt(_) => bin_bool_function::<BigInt>(first, second, |a, b| a <= b),
+        Token::Rational(_) => bin_bool_function::<Rational>(first, second, |a, b| a <= b),
         Token::Float(_) => bin_bool_function::<f64>(first, second, |a, b| a <= b),
-        _ => panic!("Cannot perform <= on non numeric types!")
+        _ => mismatch("Cannot perform <= on non numeric types!")
+    }
+}
+
+/// Three-way comparison, gives -1, 0 or 1 if the first is less than,
+/// equal to or greater than the second. NaN is treated as greater 
+/// than every other float and equal to itself
+pub fn spaceship(first: Token, second: Token) -> Result<Token, StackError> {
+    match most_generic_type(&first, &second) {
+        Token::Int(_) => bin_cmp_function::<i64>(first, second, |a, b| a.cmp(&b)),
+        Token::BigInt(_) => bin_cmp_function::<BigInt>(first, second, |a, b| a.cmp(&b)),
+        Token::Rational(_) => bin_cmp_function::<Rational>(first, second, |a, b| a.cmp(&b)),
+        Token::Float(_) => bin_cmp_function::<f64>(first, second, |a, b| {
+            a.partial_cmp(&b).unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+        }),
+        Token::Str(_) => bin_cmp_function::<String>(first, second, |a, b| a.cmp(&b)),
+        _ => mismatch("Cannot perform <=> on non numeric or string types!")
     }
 }
 
-pub fn and(first: Token, second: Token) -> Token {
+pub fn and(first: Token, second: Token) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
         Token::Bool(_) => bin_bool_function::<bool>(first, second, |a, b| a && b),
-        _ => panic!("Cannot perform & on non bool types!")
+        _ => mismatch("Cannot perform & on non bool types!")
     }
 }
 
-pub fn or(first: Token, second: Token) -> Token {
+pub fn or(first: Token, second: Token) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
         Token::Bool(_) => bin_bool_function::<bool>(first, second, |a, b| a || b),
-        _ => panic!("Cannot perform | on non bool types!")
+        _ => mismatch("Cannot perform | on non bool types!")
     }
 }
 
-pub fn xor(first: Token, second: Token) -> Token {
+pub fn xor(first: Token, second: Token) -> Result<Token, StackError> {
     match most_generic_type(&first, &second) {
         Token::Int(_) => bin_int_function(first, second, |a, b| a ^ b),
+        Token::BigInt(_) => mismatch("Cannot perform ^ on big ints, only on ints that fit in 64 bits!"),
         Token::Bool(_) => bin_bool_function::<bool>(first, second, |a, b| a ^ b),
-        _ => panic!("Cannot perform ^ on non bool or integer types!")
+        _ => mismatch("Cannot perform ^ on non bool or integer types!")
     }
 }
\ No newline at end of file
@@ -1,8 +1,27 @@
+/// Helpers shared by the tests that run source through an
+/// `Interpreter`
+#[cfg(test)]
+mod helpers {
+
+    use crate::{error::StackError, ExecError, Interpreter, Token};
+
+    /// Run the source on the interpreter, giving its whole stack,
+    /// or the kind of the runtime error without where it was raised
+    pub fn eval_on(interp: &mut Interpreter, src: &str) -> Result<Vec<Token>, StackError> {
+        match interp.eval(src) {
+            Ok(()) => Ok(interp.stack().to_vec()),
+            Err(ExecError::Stack(e)) => Err(e.kind().clone()),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+}
+
+
 #[cfg(test)]
 mod binary_operator_tests {
 
-    use std::panic::catch_unwind;
-    use crate::{stack::{self, apply_bin_op}, utils::Token};
+    use crate::{error::StackError, overflow::OverflowPolicy, stack::{self, apply_bin_op}, utils::Token};
 
     #[test]
     fn test_binary_add() {
@@ -10,15 +29,15 @@ mod binary_operator_tests {
         let mut stack = vec![
             Token::Int(5), Token::Float(5.0)
         ];
-        apply_bin_op(&mut stack, stack::add);
+        apply_bin_op(&mut stack, |a, b| stack::add(a, b, OverflowPolicy::Error)).unwrap();
         assert!(stack[0] == Token::Float(10.0));
 
         // test numbers can be concatenated as strings
         stack = vec![
             Token::Str("Hello".to_string()), Token::Int(5), Token::Str("World".to_string())
         ];
-        apply_bin_op(&mut stack, stack::add);
-        apply_bin_op(&mut stack, stack::add);
+        apply_bin_op(&mut stack, |a, b| stack::add(a, b, OverflowPolicy::Error)).unwrap();
+        apply_bin_op(&mut stack, |a, b| stack::add(a, b, OverflowPolicy::Error)).unwrap();
 
         assert!(stack[0] == Token::Str("Hello5World".to_string()))
     } 
@@ -29,24 +48,29 @@ mod binary_operator_tests {
         let mut stack = vec![
             Token::Str("HI".to_string()), Token::Int(5)
         ];
-        apply_bin_op(&mut stack, stack::mul);
+        apply_bin_op(&mut stack, |a, b| stack::mul(a, b, OverflowPolicy::Error)).unwrap();
         assert!(stack[0] == Token::Str("HIHIHIHIHI".to_string()));
 
         // test that muliplying a string by a float throws an err
-        let re = catch_unwind(|| {
-            apply_bin_op(
-                &mut vec![Token::Str("HI".to_string()), Token::Float(1.0)], 
-                stack::mul
-            )
-        });
-        assert!(re.is_err());
+        let re = apply_bin_op(
+            &mut vec![Token::Str("HI".to_string()), Token::Float(1.0)], 
+            |a, b| stack::mul(a, b, OverflowPolicy::Error)
+        );
+        assert!(matches!(re, Err(StackError::TypeMismatch(_))));
+
+        // test that repeating a string too many times is refused
+        let re = apply_bin_op(
+            &mut vec![Token::Str("ab".to_string()), Token::Int(i64::MAX)],
+            |a, b| stack::mul(a, b, OverflowPolicy::Error)
+        );
+        assert!(matches!(re, Err(StackError::BadArgument(_))));
 
         // test numbers can be concatenated as strings
         stack = vec![
             Token::Float(3.0), Token::Int(5), Token::Float(1.5)
         ];
-        apply_bin_op(&mut stack, stack::mul);
-        apply_bin_op(&mut stack, stack::mul);
+        apply_bin_op(&mut stack, |a, b| stack::mul(a, b, OverflowPolicy::Error)).unwrap();
+        apply_bin_op(&mut stack, |a, b| stack::mul(a, b, OverflowPolicy::Error)).unwrap();
 
         assert!(stack[0] == Token::Float(22.5))
     } 
@@ -54,22 +78,20 @@ mod binary_operator_tests {
     #[test]
     fn test_binary_div() {
         // test that dividing by 0 throws an err
-        let re = catch_unwind(|| {
-            apply_bin_op(
-                &mut vec![Token::Int(1), Token::Int(0)], 
-                stack::div
-            )
-        });
-        assert!(re.is_err());
+        let re = apply_bin_op(
+            &mut vec![Token::Int(1), Token::Int(0)], 
+            |a, b| stack::div(a, b, OverflowPolicy::Error)
+        );
+        assert_eq!(re, Err(StackError::DivisionByZero));
 
         // test numbers can be concatenated as strings
         let mut stack = vec![
             Token::Float(10.0), Token::Int(5), Token::Int(1)
         ];
-        apply_bin_op(&mut stack, stack::div);
+        apply_bin_op(&mut stack, |a, b| stack::div(a, b, OverflowPolicy::Error)).unwrap();
         assert_eq!(Token::Int(5), stack[stack.len() - 1]);
         
-        apply_bin_op(&mut stack, stack::div);
+        apply_bin_op(&mut stack, |a, b| stack::div(a, b, OverflowPolicy::Error)).unwrap();
         assert_eq!(Token::Float(2.0), stack[stack.len() - 1]);
     }
 
@@ -77,11 +99,11 @@ mod binary_operator_tests {
     fn test_binary_pow() {
         // test numbers can be concatenated as strings
         let mut stack = vec![Token::Float(10.0), Token::Int(3)];
-        apply_bin_op(&mut stack, stack::exp);
+        apply_bin_op(&mut stack, |a, b| stack::exp(a, b, OverflowPolicy::Error)).unwrap();
         assert_eq!(Token::Float(1000.0), stack[0]);
         
         let mut stack = vec![Token::Int(7), Token::Int(2)];
-        apply_bin_op(&mut stack, stack::exp);
+        apply_bin_op(&mut stack, |a, b| stack::exp(a, b, OverflowPolicy::Error)).unwrap();
         assert_eq!(Token::Int(49), stack[0]);
     }
 
@@ -91,17 +113,19 @@ mod binary_operator_tests {
 mod stack_exec_tests {
 
     use crate::{
-        lexer::read_stack, 
-        stack::exec_stack,
+        error::StackError,
+        lexer::{parse_data_type, read_stack, Lexeme, LexemeKind}, 
+        linalg::DimensionError,
+        stack::{exec_stack, exec_stack_with_depth, DEFAULT_MAX_DEPTH},
         utils::Token
     };
 
     #[test]
     fn test_exec_drop_dup() {
         let input = "1 2 3 DROP DUP".to_string();
-        let lexemes = read_stack(&input);
+        let lexemes = read_stack(&input).unwrap();
 
-        let res = exec_stack(&lexemes);
+        let res = exec_stack(&lexemes).unwrap();
         assert_eq!(res, vec![
             Token::Int(1), Token::Int(2), Token::Int(2)
         ])
@@ -110,18 +134,18 @@ mod stack_exec_tests {
     #[test]
     fn test_exec_full_expr() {
         let input = "1.0 2 3 9 - + 2 * + 3 *".to_string();
-        let lexemes = read_stack(&input);
+        let lexemes = read_stack(&input).unwrap();
 
-        let res = exec_stack(&lexemes);
+        let res = exec_stack(&lexemes).unwrap();
         assert_eq!(res, vec![Token::Float(-21.0)])
     }
 
     #[test]
     fn test_exec_rolld() {
         let input = "0 1 2 3 4 4 ROLLD".to_string();
-        let lexemes = read_stack(&input);
+        let lexemes = read_stack(&input).unwrap();
 
-        let res = exec_stack(&lexemes);
+        let res = exec_stack(&lexemes).unwrap();
         assert_eq!(res, vec![
             Token::Int(0), Token::Int(4), Token::Int(1), Token::Int(2), Token::Int(3)
         ])
@@ -130,10 +154,897 @@ mod stack_exec_tests {
     #[test]
     fn test_exec_equ() {
         let input = "1.0 1 ==".to_string();
-        let lexemes = read_stack(&input);
+        let lexemes = read_stack(&input).unwrap();
 
-        let res = exec_stack(&lexemes);
+        let res = exec_stack(&lexemes).unwrap();
         assert_eq!(res[0], Token::Bool(true))
     }
+
+    #[test]
+    fn test_exec_unary() {
+        let input = "2 ~ false ! 5 NEG -2.5 ABS".to_string();
+        let lexemes = read_stack(&input).unwrap();
+
+        let res = exec_stack(&lexemes).unwrap();
+        assert_eq!(res, vec![
+            Token::Int(-3), Token::Bool(true), Token::Int(-5), Token::Float(2.5)
+        ]);
+
+        let re = exec_stack(&read_stack("1 !").unwrap());
+        assert!(matches!(re.unwrap_err().kind(), StackError::TypeMismatch(_)));
+    }
+
+    #[test]
+    fn test_exec_quoted() {
+        let res = exec_stack(&read_stack("1 2 '+").unwrap()).unwrap();
+        assert_eq!(res, vec![
+            Token::Int(1), Token::Int(2), Token::Quoted("+".to_string())
+        ]);
+
+        let res = exec_stack(&read_stack("2 3 '4 '+ EVAL '* EVAL").unwrap()).unwrap();
+        assert_eq!(res, vec![Token::Int(14)]);
+
+        // operators can be selected like any other value
+        let res = exec_stack(&read_stack("3 4 '+ '* true IFELSE EVAL").unwrap()).unwrap();
+        assert_eq!(res, vec![Token::Int(7)]);
+    }
+
+    #[test]
+    fn test_exec_lambda() {
+        let res = exec_stack(&read_stack("0 1 2 2 1 + {3 | x2 x1 x0 1 + * * }").unwrap()).unwrap();
+        assert_eq!(res, vec![Token::Int(0), Token::Int(12)]);
+
+        // a quoted lambda is only applied once it is EVAL'd
+        let res = exec_stack(&read_stack("3 4 '{2 | x0 x1 -}").unwrap()).unwrap();
+        assert!(matches!(res[2], Token::Lambda(_)));
+
+        let res = exec_stack(&read_stack("3 4 '{2 | x0 x1 -} EVAL").unwrap()).unwrap();
+        assert_eq!(res, vec![Token::Int(-1)]);
+
+        let re = exec_stack(&read_stack("1 {2 | x0 x1 +}").unwrap());
+        assert_eq!(re.unwrap_err().kind(), &StackError::Underflow { needed: 2, found: 1 });
+    }
+
+    #[test]
+    fn test_exec_recursive_lambda() {
+        let input = "1 5 { 2 | x0 x1 * x1 1 - DUP 0 > SELF 'DROP ROT IFELSE EVAL}".to_string();
+        let res = exec_stack(&read_stack(&input).unwrap()).unwrap();
+        assert_eq!(res, vec![Token::Int(120)]);
+
+        // unbounded recursion is stopped at the depth limit
+        let re = exec_stack(&read_stack("{0 | SELF EVAL}").unwrap());
+        assert_eq!(re.unwrap_err().kind(), &StackError::DepthExceeded(DEFAULT_MAX_DEPTH));
+
+        let res = exec_stack_with_depth(&read_stack(&input).unwrap(), 5).unwrap();
+        assert_eq!(res, vec![Token::Int(120)]);
+        let re = exec_stack_with_depth(&read_stack(&input).unwrap(), 4);
+        assert_eq!(re.unwrap_err().kind(), &StackError::DepthExceeded(4));
+
+        let re = exec_stack(&read_stack("SELF").unwrap());
+        assert!(matches!(re.unwrap_err().kind(), StackError::BadArgument(_)));
+    }
+
+    #[test]
+    fn test_exec_spaceship() {
+        let input = "2 5 5 <=> 3 + * 3 << 1.5 1 <=> \"a\" \"b\" <=> NaN 1.0 <=>".to_string();
+        let lexemes = read_stack(&input).unwrap();
+
+        let res = exec_stack(&lexemes).unwrap();
+        assert_eq!(res, vec![
+            Token::Int(48), Token::Int(1), Token::Int(-1), Token::Int(1)
+        ])
+    }
+
+    #[test]
+    fn test_exec_errors() {
+        let re = exec_stack(&read_stack("1 2 + FOO").unwrap());
+        assert_eq!(re, Err(StackError::Located {
+            lexeme: Lexeme { text: "FOO".to_string(), line: 1, col: 7, span: 6..9, kind: LexemeKind::Code },
+            error: Box::new(StackError::UnknownWord("FOO".to_string())),
+        }));
+
+        let re = exec_stack(&read_stack("1 DROP DROP").unwrap());
+        assert_eq!(re.unwrap_err().kind(), &StackError::Underflow { needed: 1, found: 0 });
+
+        let re = exec_stack(&read_stack("1 2 3 -1 ROLL").unwrap());
+        assert!(matches!(re.unwrap_err().kind(), StackError::BadArgument(_)));
+
+        // errors inside a lambda point at the lexeme in its body
+        let re = exec_stack(&read_stack("1 {1 | x0 0 %}").unwrap());
+        assert_eq!(re, Err(StackError::Located {
+            lexeme: Lexeme { text: "%".to_string(), line: 1, col: 13, span: 12..13, kind: LexemeKind::Code },
+            error: Box::new(StackError::DivisionByZero),
+        }));
+    }
+
+    #[test]
+    fn test_exec_vectors() {
+        let input = "[1, 2, 3] [4, 3, 2] + [1, 2, 3] [4, 3, 2] *".to_string();
+        let lexemes = read_stack(&input).unwrap();
+
+        let res = exec_stack(&lexemes).unwrap();
+        assert_eq!(res, vec![
+            Token::Vector(vec![Token::Int(5), Token::Int(5), Token::Int(5)]),
+            Token::Int(16)
+        ])
+    }
+
+    #[test]
+    fn test_exec_cross() {
+        let res = exec_stack(&read_stack("[1, 2, 3] [4.0, 5, 6] x").unwrap()).unwrap();
+        assert_eq!(res, vec![
+            Token::Vector(vec![Token::Float(-3.0), Token::Float(6.0), Token::Float(-3.0)])
+        ]);
+
+        let re = exec_stack(&read_stack("[1, 2] [3, 4] x").unwrap());
+        assert_eq!(re.unwrap_err().kind(), &StackError::Dimension(DimensionError::CrossProduct(2, 2)));
+    }
+
+    #[test]
+    fn test_exec_matrices() {
+        let input = "[[1, 2, 3], [4, 5, 6]] DUP [1, 0, 1] * SWAP TRANSP".to_string();
+        let lexemes = read_stack(&input).unwrap();
+
+        let res = exec_stack(&lexemes).unwrap();
+        assert_eq!(res, vec![
+            Token::Vector(vec![Token::Int(4), Token::Int(10)]),
+            Token::Matrix(vec![
+                vec![Token::Int(1), Token::Int(4)],
+                vec![Token::Int(2), Token::Int(5)],
+                vec![Token::Int(3), Token::Int(6)],
+            ])
+        ]);
+
+        // ragged matrices are rejected by the lexer, and explained
+        // when they are executed
+        assert_eq!(parse_data_type("[[1, 2], [3]]"), None);
+        let re = exec_stack(&read_stack("[[1, 2], [3]]").unwrap());
+        assert_eq!(
+            re.unwrap_err().kind(),
+            &StackError::Syntax("Every row of a matrix must be the same length, found rows of 2 and 1!".to_string())
+        );
+        let re = exec_stack(&read_stack("[[1, 2], 3]").unwrap());
+        assert_eq!(re.unwrap_err().kind(), &StackError::Syntax("Cannot mix vectors and values in a matrix!".to_string()));
+
+        // anything nested deeper is refused before it is parsed, so
+        // that it cannot take exponential time or overflow the stack
+        let deep = format!("{}1{}", "[".repeat(20000), "]".repeat(20000));
+        assert_eq!(parse_data_type(&deep), None);
+        let re = exec_stack(&read_stack(&deep).unwrap());
+        assert_eq!(re.unwrap_err().kind(), &StackError::Syntax("Cannot nest vectors more than 2 deep!".to_string()));
+
+        // and values are written as they would be printed
+        let re = exec_stack(&read_stack("[[1, 2], [3, 4]] 2 *").unwrap());
+        assert_eq!(re.unwrap_err().kind(), &StackError::TypeMismatch("Cannot multiply [[1, 2], [3, 4]] by 2!".to_string()));
+
+        // as are products with mismatched dimensions
+        let re = exec_stack(&read_stack("[[1, 2]] [[1, 2]] *").unwrap());
+        assert_eq!(
+            re.unwrap_err().kind(), 
+            &StackError::Dimension(DimensionError::MatrixProduct((1, 2), (1, 2)))
+        );
+    }
     
 }
+
+#[cfg(test)]
+mod lexer_tests {
+
+    use crate::{
+        lexer::{read_stack, LexError},
+        stack::exec_stack,
+        utils::Token
+    };
+
+    #[test]
+    fn test_read_strings() {
+        let input = "\"Hello \" \"World\" +\n\"a\\tb\\\"\\u{1F600}\"".to_string();
+        let lexemes = read_stack(&input).unwrap();
+        assert_eq!(lexemes.len(), 4);
+
+        let res = exec_stack(&lexemes).unwrap();
+        assert_eq!(res, vec![
+            Token::Str("Hello World".to_string()),
+            Token::Str("a\tb\"\u{1F600}".to_string())
+        ]);
+        assert_eq!(res[1].to_string(), "\"a\\tb\\\"\u{1F600}\"");
+    }
+
+    #[test]
+    fn test_read_string_errors() {
+        let err = read_stack("1 2\n  \"oops 3 +").unwrap_err();
+        assert_eq!(err, LexError { message: "Unterminated string".to_string(), line: 2, col: 3 });
+
+        let err = read_stack("\"ok\" \"bad \\q\"").unwrap_err();
+        assert_eq!((err.line, err.col), (1, 11));
+
+        assert!(read_stack("\"\\u{110000}\"").is_err());
+    }
+
+    #[test]
+    fn test_read_unterminated_brackets() {
+        let err = read_stack("1 {1 | x0\n2 3 +\n4 5 +").unwrap_err();
+        assert_eq!(err, LexError { message: "Unterminated lambda".to_string(), line: 1, col: 3 });
+
+        let err = read_stack("[1, 2]\n  [[3, 4], [5, 6]").unwrap_err();
+        assert_eq!(err, LexError { message: "Unterminated vector".to_string(), line: 2, col: 3 });
+    }
+
+}
+
+#[cfg(test)]
+mod runner_tests {
+
+    use crate::runner::{render_stack_file, unified_diff, split_stack_lines};
+
+    #[test]
+    fn test_unified_diff() {
+        let expected = vec!["1".to_string(), "2".to_string(), "3".to_string()];
+        let actual = vec!["1".to_string(), "4".to_string(), "3".to_string()];
+
+        let diff = unified_diff(&expected, &actual, "expected", "actual");
+        assert_eq!(diff, "--- expected\n+++ actual\n@@ -1,3 +1,3 @@\n 1\n-2\n+4\n 3\n");
+
+        let diff = unified_diff(&[], &actual[..1], "expected", "actual");
+        assert_eq!(diff, "--- expected\n+++ actual\n@@ -0,0 +1,1 @@\n+1\n");
+    }
+
+    #[test]
+    fn test_render_stack_file() {
+        let stack = vec!["1".to_string(), "\"Hello World\"".to_string(), "[5, 5, 5]".to_string()];
+        assert_eq!(render_stack_file(&stack), "1\n\"Hello World\"\n[5, 5, 5]\n");
+        assert_eq!(render_stack_file(&[]), "");
+    }
+
+    #[test]
+    fn test_split_stack_lines() {
+        let expected = vec!["1".to_string(), "\"a b\"".to_string()];
+        assert_eq!(split_stack_lines("1\n\"a b\"\n"), expected);
+        assert_eq!(split_stack_lines("1\r\n\"a b\"  \r\n"), expected);
+        assert_eq!(split_stack_lines("1 \n\n\"a b\""), expected);
+        assert!(split_stack_lines("").is_empty());
+        assert!(split_stack_lines("\r\n").is_empty());
+    }
+
+}
+
+#[cfg(test)]
+mod repl_tests {
+
+    use crate::repl::Repl;
+
+    /// Feed each line to the repl, giving everything it wrote
+    fn run_lines(repl: &mut Repl, lines: &[&str]) -> String {
+        let mut out = Vec::new();
+        for line in lines {
+            repl.handle_line(line, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_repl_keeps_stack() {
+        let mut repl = Repl::new();
+        let out = run_lines(&mut repl, &["1 2", "+", ":stack"]);
+        assert_eq!(out, "1\n2\n3\n3\n");
+    }
+
+    #[test]
+    fn test_repl_errors_and_undo() {
+        let mut repl = Repl::new();
+        let out = run_lines(&mut repl, &["1 2", "DROP DROP DROP"]);
+        assert!(out.ends_with(
+            "Runtime error: Stack underflow: needed 1 elements but found 0! (at 'DROP', line 1, column 11)\n  |\n1 | DROP DROP DROP\n  |           ^^^^\n"
+        ));
+        assert_eq!(run_lines(&mut repl, &[":stack"]), "1\n2\n");
+
+        let out = run_lines(&mut repl, &["+", ":undo"]);
+        assert_eq!(out, "3\n1\n2\n");
+
+        let out = run_lines(&mut repl, &[":clear", ":undo", ":undo", ":undo"]);
+        assert_eq!(out, "<empty>\n1\n2\n<empty>\nNothing to undo\n");
+    }
+
+    #[test]
+    fn test_repl_quit_and_unknown() {
+        let mut repl = Repl::new();
+        let mut out = Vec::new();
+        assert!(repl.handle_line(":nope", &mut out).unwrap());
+        assert!(!repl.handle_line(":quit", &mut out).unwrap());
+        assert_eq!(String::from_utf8(out).unwrap(), "Error: unknown command ':nope'\n");
+    }
+
+    #[test]
+    fn test_repl_defines_words() {
+        let mut repl = Repl::new();
+        let out = run_lines(&mut repl, &[": SQUARE DUP * ;", "3 SQUARE", ":words"]);
+        assert!(out.starts_with("<empty>\n9\n"));
+        assert!(out.ends_with(": SQUARE DUP * ;\n"));
+    }
+
+}
+
+
+#[cfg(test)]
+mod cli_tests {
+
+    use crate::{cli::{self, exit_code, parse_args, Command}, runner::exec};
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse(&[]), Ok(Command::Repl));
+        assert_eq!(parse(&["run", "script.txt"]), Ok(Command::Run("script.txt".to_string())));
+        assert_eq!(parse(&["run", "-"]), Ok(Command::Run("-".to_string())));
+        assert_eq!(parse(&["001"]), Ok(Command::Case("001".to_string())));
+        assert_eq!(parse(&["output", "230"]), Ok(Command::Output("230".to_string())));
+        assert_eq!(parse(&["test-all"]), Ok(Command::TestAll));
+        assert_eq!(parse(&["run", "x", "--help"]), Ok(Command::Help));
+
+        assert!(parse(&["run"]).is_err());
+        assert!(parse(&["output", "1"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+        assert!(parse(&["1234"]).is_err());
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(exit_code(&exec("1 \"a").unwrap_err()), cli::PARSE_ERROR);
+        assert_eq!(exit_code(&exec("1 +").unwrap_err()), cli::RUNTIME_ERROR);
+        assert_eq!(exec("1 2 +"), Ok(vec!["3".to_string()]));
+    }
+
+}
+
+
+#[cfg(test)]
+mod interpreter_tests {
+
+    use crate::{ExecError, Interpreter, Token};
+
+    #[test]
+    fn test_interpreter_keeps_stack() {
+        let mut interp = Interpreter::new();
+        interp.eval("1 2").unwrap();
+        interp.push(Token::Int(3));
+        interp.eval("+ +").unwrap();
+        assert_eq!(interp.stack(), &[Token::Int(6)]);
+        assert_eq!(interp.pop(), Some(Token::Int(6)));
+        assert!(interp.stack().is_empty());
+    }
+
+    #[test]
+    fn test_interpreter_errors_restore_stack() {
+        let mut interp = Interpreter::new();
+        interp.eval("1 2").unwrap();
+
+        assert!(matches!(interp.eval("3 DROP DROP DROP DROP"), Err(ExecError::Stack(_))));
+        assert!(matches!(interp.eval("\"oops"), Err(ExecError::Lex(_))));
+        assert_eq!(interp.stack(), &[Token::Int(1), Token::Int(2)]);
+    }
+
+}
+
+
+#[cfg(test)]
+mod registry_tests {
+
+    use crate::{
+        error::StackError,
+        registry::{Registry, RegistryError, StackEffect},
+        Interpreter, Token
+    };
+
+    #[test]
+    fn test_register_host_word() {
+        let mut interp = Interpreter::new();
+        interp.register("DEPTH", 0, 1, |stack| {
+            stack.push(Token::Int(stack.len() as i64));
+            Ok(())
+        }).unwrap();
+        interp.register("HALVE", 1, 1, |stack| {
+            match stack.pop() {
+                Some(Token::Int(val)) => {
+                    stack.push(Token::Int(val / 2));
+                    Ok(())
+                },
+                _ => Err(StackError::TypeMismatch("HALVE needs an int!".to_string())),
+            }
+        }).unwrap();
+
+        interp.eval("7 8 DEPTH HALVE { 1 | x0 HALVE } EVAL").unwrap();
+        assert_eq!(interp.stack(), &[Token::Int(7), Token::Int(8), Token::Int(0)]);
+
+        interp.clear();
+        let err = interp.eval("DEPTH DROP HALVE").unwrap_err().to_string();
+        assert_eq!(err, "Runtime error: Stack underflow: needed 1 elements but found 0! (at 'HALVE', line 1, column 12)");
+    }
+
+    #[test]
+    fn test_register_collisions() {
+        let mut ops = Registry::builtins();
+        let noop = |_: &mut Vec<Token>| Ok(());
+
+        assert_eq!(ops.register("DUP", 1, 2, noop), Err(RegistryError::AlreadyDefined("DUP".to_string())));
+        for name in ["", "1", "true", "\"s\"", "'Q", "a b", "x2", "EVAL", "SELF"] {
+            assert_eq!(ops.register(name, 0, 0, noop), Err(RegistryError::InvalidName(name.to_string())));
+        }
+        assert!(ops.register("NOOP", 0, 0, noop).is_ok());
+        assert!(ops.register("xs", 0, 0, noop).is_ok());
+    }
+
+    #[test]
+    fn test_list_words() {
+        let ops = Registry::builtins();
+        let words = ops.words().collect::<Vec<(&str, StackEffect)>>();
+        assert!(words.contains(&("+", StackEffect { inputs: 2, outputs: 1 })));
+        assert!(words.contains(&("DUP", StackEffect { inputs: 1, outputs: 2 })));
+        assert_eq!(StackEffect { inputs: 2, outputs: 1 }.to_string(), "( 2 -- 1 )");
+        assert!(Registry::new().words().next().is_none());
+    }
+
+}
+
+
+#[cfg(test)]
+mod definition_tests {
+
+    use crate::{error::StackError, Interpreter, Token};
+    use super::helpers::eval_on;
+
+    #[test]
+    fn test_define_words() {
+        let mut interp = Interpreter::new();
+        interp.eval(": SQUARE DUP * ; 3 SQUARE 'SQUARE").unwrap();
+        interp.eval("EVAL").unwrap();
+        assert_eq!(interp.stack(), &[Token::Int(81)]);
+
+        // later calls see the new definition
+        interp.eval(": SQUARE DROP 0 ; 3 SQUARE WORDS").unwrap();
+        assert_eq!(interp.stack(), &[
+            Token::Int(81), Token::Int(0), Token::Vector(vec![Token::Str("SQUARE".to_string())])
+        ]);
+    }
+
+    #[test]
+    fn test_recursive_word() {
+        let mut interp = Interpreter::new();
+        interp.eval(": FACT DUP 1 > '{ 1 | x0 DUP 1 - FACT * } '{ 1 | x0 } ROT IFELSE EVAL ;").unwrap();
+        interp.eval("6 FACT").unwrap();
+        assert_eq!(interp.stack(), &[Token::Int(720)]);
+
+        let mut interp = Interpreter::with_max_depth(10);
+        let err = interp.eval(": FOREVER FOREVER ; FOREVER").unwrap_err();
+        assert_eq!(err.to_string(), "Runtime error: Exceeded the maximum call depth of 10! (at 'FOREVER', line 1, column 11)");
+        assert_eq!(interp.definitions().count(), 0);
+    }
+
+    #[test]
+    fn test_bad_definitions() {
+        let mut interp = Interpreter::new();
+        for (src, msg) in [
+            (": DUP 1 ;", "The word 'DUP' is already defined!"),
+            (": 12 1 ;", "Cannot use '12' as the name of a word!"),
+            (": ;", "Missing a name after ':'!"),
+            (": A 1", "Missing ';' to end the definition!"),
+            (": A : B ; ;", "Cannot define a word inside another definition!"),
+            ("1 ;", "Found ';' outside of a definition!"),
+            // nor may a word hide a variable, as a variable cannot hide a word
+            ("1 ->a : a 2 ;", "The variable 'a' is already defined!"),
+            ("{0 | 1 ->b : b 2 ; }", "The variable 'b' is already defined!"),
+        ] {
+            assert_eq!(eval_on(&mut interp, src), Err(StackError::Syntax(msg.to_string())));
+        }
+
+        // words cannot see the arguments of the lambda calling them
+        interp.eval(": ARG x0 ;").unwrap();
+        assert!(interp.eval("1 { 1 | ARG }").is_err());
+    }
+
+}
+
+
+#[cfg(test)]
+mod control_flow_tests {
+
+    use crate::{error::StackError, Interpreter, Token};
+    use super::helpers::eval_on;
+
+    #[test]
+    fn test_if_else_then() {
+        assert_eq!(eval_on(&mut Interpreter::new(), "3 4 < IF 1 ELSE 2 THEN"), Ok(vec![Token::Int(1)]));
+        assert_eq!(eval_on(&mut Interpreter::new(), "3 4 > IF 1 ELSE 2 THEN"), Ok(vec![Token::Int(2)]));
+        assert_eq!(eval_on(&mut Interpreter::new(), "false IF 1 THEN"), Ok(vec![]));
+        assert_eq!(eval_on(&mut Interpreter::new(), "true IF false IF 1 ELSE 2 THEN ELSE 3 THEN"), Ok(vec![Token::Int(2)]));
+
+        // only the taken branch runs, so the other may fail
+        assert_eq!(eval_on(&mut Interpreter::new(), "true IF 1 ELSE DROP DROP THEN"), Ok(vec![Token::Int(1)]));
+
+        assert_eq!(
+            eval_on(&mut Interpreter::new(), "1 IF 1 THEN"),
+            Err(StackError::TypeMismatch("Cannot convert 1 to bool!".to_string()))
+        );
+        assert_eq!(
+            eval_on(&mut Interpreter::new(), "true IF 1"),
+            Err(StackError::Syntax("Missing THEN to close IF!".to_string()))
+        );
+        assert_eq!(
+            eval_on(&mut Interpreter::new(), "1 ELSE"),
+            Err(StackError::Syntax("Found 'ELSE' without a matching IF!".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        assert_eq!(eval_on(&mut Interpreter::new(), "1 WHILE DUP 100 < DO 2 * END"), Ok(vec![Token::Int(128)]));
+        assert_eq!(eval_on(&mut Interpreter::new(), "0 5 0 DO I + LOOP"), Ok(vec![Token::Int(10)]));
+        assert_eq!(eval_on(&mut Interpreter::new(), "0 0 DO I LOOP"), Ok(vec![]));
+        assert_eq!(eval_on(&mut Interpreter::new(), "2 0 DO 2 0 DO I LOOP LOOP"), Ok(vec![
+            Token::Int(0), Token::Int(1), Token::Int(0), Token::Int(1)
+        ]));
+        // the DO of a counted loop in a WHILE condition is not the
+        // one that starts the body
+        assert_eq!(eval_on(&mut Interpreter::new(), "0 WHILE 2 0 DO LOOP DUP 3 < DO 1 + END"), Ok(vec![Token::Int(3)]));
+        assert_eq!(
+            eval_on(&mut Interpreter::new(), "0 WHILE DUP 2 < DO 2 0 DO LOOP 1 + END 5"),
+            Ok(vec![Token::Int(2), Token::Int(5)])
+        );
+        assert_eq!(eval_on(&mut Interpreter::new(), "1 4 TIMES { 3 * }"), Ok(vec![Token::Int(81)]));
+        assert_eq!(eval_on(&mut Interpreter::new(), "2 3 TIMES {1 | x0 x0 +}"), Ok(vec![Token::Int(16)]));
+        assert_eq!(eval_on(&mut Interpreter::new(), "5 { 1 | 2 TIMES { x0 } }"), Ok(vec![Token::Int(5), Token::Int(5)]));
+
+        // a counted loop in place of recursion
+        assert_eq!(
+            eval_on(&mut Interpreter::new(), ": FACT 1 SWAP 1 + 1 DO I * LOOP ; 6 FACT"),
+            Ok(vec![Token::Int(720)])
+        );
+
+        assert_eq!(
+            eval_on(&mut Interpreter::new(), "I"),
+            Err(StackError::BadArgument("Cannot use I outside of a DO loop!".to_string()))
+        );
+        assert_eq!(
+            eval_on(&mut Interpreter::new(), "3 TIMES"),
+            Err(StackError::Syntax("TIMES must be followed by a { ... } block!".to_string()))
+        );
+        assert_eq!(
+            eval_on(&mut Interpreter::new(), "WHILE true END"),
+            Err(StackError::Syntax("Missing DO after WHILE!".to_string()))
+        );
+    }
+
+
+    #[test]
+    fn test_nested_blocks_count_toward_depth() {
+        assert_eq!(
+            eval_on(&mut Interpreter::new(), ": R true IF true IF true IF 1 TIMES { 1 TIMES { R } } THEN THEN THEN ; R"),
+            Err(StackError::DepthExceeded(500))
+        );
+        let nested = format!(": R {}R{} ; R", "1 TIMES { ".repeat(30), " }".repeat(30));
+        assert_eq!(eval_on(&mut Interpreter::new(), &nested), Err(StackError::DepthExceeded(500)));
+
+        // blocks nested well within the limit still run
+        assert_eq!(
+            eval_on(&mut Interpreter::new(), "0 3 0 DO 2 0 DO true IF 1 TIMES { 1 + } THEN LOOP LOOP"),
+            Ok(vec![Token::Int(6)])
+        );
+    }
+
+}
+
+
+#[cfg(test)]
+mod variable_tests {
+
+    use crate::{error::StackError, Interpreter, Token};
+    use super::helpers::eval_on;
+
+    #[test]
+    fn test_store_and_fetch() {
+        let mut interp = Interpreter::new();
+        interp.eval("5 'n STORE 'n FETCH 2 ->m n m *").unwrap();
+        assert_eq!(interp.stack(), &[Token::Int(5), Token::Int(10)]);
+        assert_eq!(interp.var("n"), Some(&Token::Int(5)));
+
+        interp.eval("\"a\" ->n n").unwrap();
+        assert_eq!(interp.stack().last(), Some(&Token::Str("a".to_string())));
+
+        assert_eq!(eval_on(&mut interp, "'k FETCH").unwrap_err(), StackError::UndefinedVariable("k".to_string()));
+        assert!(matches!(eval_on(&mut interp, "1 ->DUP").unwrap_err(), StackError::BadArgument(_)));
+        interp.eval(": SQ DUP * ;").unwrap();
+        assert_eq!(
+            eval_on(&mut interp, "1 ->SQ").unwrap_err(),
+            StackError::BadArgument("The word 'SQ' is already defined!".to_string())
+        );
+        assert!(matches!(eval_on(&mut interp, "1 2 STORE").unwrap_err(), StackError::TypeMismatch(_)));
+    }
+
+    #[test]
+    fn test_variable_scopes() {
+        let mut interp = Interpreter::new();
+        interp.eval("0 ->total 1 { 1 | x0 ->step total step + ->total }").unwrap();
+        interp.eval("total").unwrap();
+        assert_eq!(interp.stack(), &[Token::Int(1)]);
+
+        // a variable first stored in a lambda is local to it
+        assert!(interp.var("step").is_none());
+        assert_eq!(eval_on(&mut interp, "step").unwrap_err(), StackError::UnknownWord("step".to_string()));
+
+        interp.eval(": COUNTDOWN ->left WHILE left 0 > DO left left 1 - ->left END ; 3 COUNTDOWN").unwrap();
+        assert_eq!(interp.stack(), &[Token::Int(1), Token::Int(3), Token::Int(2), Token::Int(1)]);
+        assert!(interp.var("left").is_none());
+    }
+
+}
+
+
+#[cfg(test)]
+mod position_tests {
+
+    use crate::{lexer::{read_stack, Lexeme, LexemeKind}, Interpreter};
+
+    #[test]
+    fn test_lexeme_positions() {
+        let lexemes = read_stack("1 \"a b\"\n  [1, 2] é +").unwrap();
+        assert_eq!(lexemes, vec![
+            Lexeme { text: "1".to_string(), line: 1, col: 1, span: 0..1, kind: LexemeKind::Code },
+            Lexeme { text: "\"a b\"".to_string(), line: 1, col: 3, span: 2..7, kind: LexemeKind::Code },
+            Lexeme { text: "[1, 2]".to_string(), line: 2, col: 3, span: 10..16, kind: LexemeKind::Code },
+            Lexeme { text: "é".to_string(), line: 2, col: 10, span: 17..19, kind: LexemeKind::Code },
+            Lexeme { text: "+".to_string(), line: 2, col: 12, span: 20..21, kind: LexemeKind::Code },
+        ]);
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let src = "1 2 +\n\t3 {1 |\n  x0 0 /}";
+        let err = Interpreter::new().eval(src).unwrap_err();
+        assert_eq!(err.render(src), "\
+Runtime error: Cannot divide by 0! (at '/', line 3, column 8)
+  |
+3 |   x0 0 /}
+  |        ^");
+
+        let src = "1 2\n\t3 TIMES { FOO }";
+        let err = Interpreter::new().eval(src).unwrap_err();
+        assert_eq!(err.render(src), "\
+Runtime error: Unsupported stack element: 'FOO' (at 'FOO', line 2, column 12)
+  |
+2 | \t3 TIMES { FOO }
+  | \t          ^^^");
+
+        // a word defined earlier is not underlined in the new source
+        let mut interp = Interpreter::new();
+        interp.eval(": W 1 2 3 0 / ;").unwrap();
+        let err = interp.eval("W").unwrap_err();
+        assert_eq!(err.render("W"), "Runtime error: Cannot divide by 0! (at '/', line 1, column 13)");
+
+        let src = "\"oops";
+        let err = Interpreter::new().eval(src).unwrap_err();
+        assert_eq!(err.render(src), "Parse error: Unterminated string at line 1, column 1\n  |\n1 | \"oops\n  | ^");
+    }
+
+}
+
+
+#[cfg(test)]
+mod comment_tests {
+
+    use crate::{lexer::{read_stack, LexError, LexemeKind}, Interpreter, Token};
+
+    #[test]
+    fn test_comments_kept_as_trivia() {
+        let lexemes = read_stack("# header\n1 ( a -- b ) 2 // add\n+").unwrap();
+        let kinds = lexemes.iter()
+            .map(|lexeme| (lexeme.text.as_str(), lexeme.kind))
+            .collect::<Vec<(&str, LexemeKind)>>();
+        assert_eq!(kinds, vec![
+            ("# header", LexemeKind::Comment),
+            ("1", LexemeKind::Code),
+            ("( a -- b )", LexemeKind::Comment),
+            ("2", LexemeKind::Code),
+            ("// add", LexemeKind::Comment),
+            ("+", LexemeKind::Code),
+        ]);
+        assert_eq!((lexemes[4].line, lexemes[4].col, lexemes[4].span.clone()), (2, 16, 24..30));
+
+        let err = read_stack("1 ( never closed").unwrap_err();
+        assert_eq!(err, LexError { message: "Unterminated comment".to_string(), line: 1, col: 3 });
+    }
+
+    #[test]
+    fn test_comments_are_not_executed() {
+        let mut interp = Interpreter::new();
+        interp.eval("\
+# squares its argument
+: SQUARE ( n -- n*n ) DUP * ;
+3 SQUARE // 9
+{1 | x0 # a comment may hide a }
+  1 + }
+\"# kept\"").unwrap();
+        assert_eq!(interp.stack(), &[Token::Int(10), Token::Str("# kept".to_string())]);
+
+        // only the start of a word begins a comment
+        assert!(interp.eval("a#b").is_err());
+        assert!(interp.eval("(1)").is_err());
+    }
+
+}
+
+
+#[cfg(test)]
+mod overflow_tests {
+
+    use crate::{error::StackError, Interpreter, OverflowPolicy, Token};
+    use super::helpers::eval_on;
+
+    fn eval_with(overflow: OverflowPolicy, src: &str) -> Result<Vec<Token>, StackError> {
+        let mut interp = Interpreter::new();
+        interp.set_overflow(overflow);
+        eval_on(&mut interp, src)
+    }
+
+    #[test]
+    fn test_overflow_policies() {
+        let max = "9223372036854775807";
+        let src = format!("{} 1 +", max);
+        assert_eq!(eval_with(OverflowPolicy::Error, &src), Err(StackError::Overflow));
+        assert_eq!(eval_with(OverflowPolicy::Wrap, &src), Ok(vec![Token::Int(i64::MIN)]));
+        assert_eq!(eval_with(OverflowPolicy::Saturate, &src), Ok(vec![Token::Int(i64::MAX)]));
+        assert_eq!(eval_with(OverflowPolicy::Float, &src), Ok(vec![Token::Float(9223372036854775808.0)]));
+
+        // every integer operator follows the policy, including
+        // those inside vectors
+        let min = "-9223372036854775808";
+        assert_eq!(eval_with(OverflowPolicy::Error, &format!("{} -1 /", min)), Err(StackError::Overflow));
+        assert_eq!(eval_with(OverflowPolicy::Error, &format!("{} NEG", min)), Err(StackError::Overflow));
+        assert_eq!(eval_with(OverflowPolicy::Saturate, &format!("{} ABS", min)), Ok(vec![Token::Int(i64::MAX)]));
+        assert_eq!(eval_with(OverflowPolicy::Error, &format!("{} -1 %", min)), Ok(vec![Token::Int(0)]));
+        assert_eq!(
+            eval_with(OverflowPolicy::Wrap, &format!("[{}, 1] [2, 2] *", max)),
+            Ok(vec![Token::Int(0)])
+        );
+        assert_eq!(eval_with(OverflowPolicy::Error, &format!("[{}] [1] +", max)), Err(StackError::Overflow));
+    }
+
+    #[test]
+    fn test_checked_exponent_and_shift() {
+        assert_eq!(eval_with(OverflowPolicy::Error, "2 62 **"), Ok(vec![Token::Int(1 << 62)]));
+        assert_eq!(eval_with(OverflowPolicy::Error, "2 64 **"), Err(StackError::Overflow));
+        assert_eq!(eval_with(OverflowPolicy::Wrap, "3 2 64 ** **"), Ok(vec![Token::Int(1)]));
+        assert_eq!(eval_with(OverflowPolicy::Wrap, "2 5000000000 **"), Ok(vec![Token::Int(0)]));
+        assert_eq!(eval_with(OverflowPolicy::Saturate, "-2 65 **"), Ok(vec![Token::Int(i64::MIN)]));
+        assert_eq!(eval_with(OverflowPolicy::Error, "-1 5000000001 **"), Ok(vec![Token::Int(-1)]));
+        assert!(matches!(eval_with(OverflowPolicy::Float, "2 -1 **"), Err(StackError::BadArgument(_))));
+
+        assert_eq!(eval_with(OverflowPolicy::Error, "1 62 <<"), Ok(vec![Token::Int(1 << 62)]));
+        assert_eq!(eval_with(OverflowPolicy::Error, "1 63 <<"), Err(StackError::Overflow));
+        assert_eq!(eval_with(OverflowPolicy::Wrap, "3 62 <<"), Ok(vec![Token::Int(i64::MIN + (1 << 62))]));
+        assert_eq!(eval_with(OverflowPolicy::Saturate, "-3 62 <<"), Ok(vec![Token::Int(i64::MIN)]));
+        assert_eq!(eval_with(OverflowPolicy::BigInt, "3 62 <<").map(|res| res[0].to_string()), Ok("13835058055282163712".to_string()));
+        assert!(matches!(eval_with(OverflowPolicy::Error, "1 64 <<"), Err(StackError::BadArgument(_))));
+        assert!(matches!(eval_with(OverflowPolicy::Error, "8 -1 >>"), Err(StackError::BadArgument(_))));
+    }
+
+}
+
+
+#[cfg(test)]
+mod bigint_tests {
+
+    use crate::{bigint::BigInt, error::StackError, Interpreter, Token};
+    use super::helpers::eval_on;
+
+    fn big(val: &str) -> Token {
+        Token::BigInt(val.parse().unwrap())
+    }
+
+    #[test]
+    fn test_bigint_promote_and_demote() {
+        let mut interp = Interpreter::new();
+        interp.eval("\
+1 25 {2 | x0 x1 * x1 1 - DUP 0 > SELF 'DROP ROT IFELSE EVAL} EVAL
+9223372036854775807 1 + 2 -").unwrap();
+        assert_eq!(interp.pop(), Some(Token::Int(i64::MAX - 1)));
+        assert_eq!(interp.pop(), Some(big("15511210043330985984000000")));
+
+        interp.clear();
+        interp.eval("100000000000000000000 DUP 7 % SWAP -7 / 2 64 ** 2 64 ** 1 - > -9223372036854775808 NEG").unwrap();
+        assert_eq!(interp.stack(), &[
+            Token::Int(2),
+            big("-14285714285714285714"),
+            Token::Bool(true),
+            big("9223372036854775808"),
+        ]);
+        assert_eq!(interp.stack()[1].to_string(), "-14285714285714285714");
+    }
+
+    #[test]
+    fn test_bigint_division_signs() {
+        let (a, b): (BigInt, BigInt) = ("-123456789012345678901234567890".parse().unwrap(), BigInt::from(-97i64));
+        let (quot, rem) = a.div_rem(&b).unwrap();
+        assert_eq!(quot.to_string(), "1272750402189130710322005854");
+        assert_eq!(rem.to_string(), "-52");
+        assert_eq!(&(&quot * &b) + &rem, a);
+        assert!(a.div_rem(&BigInt::default()).is_none());
+    }
+
+    #[test]
+    fn test_bigint_bitwise() {
+        let mut interp = Interpreter::new();
+        interp.eval("2 100 ** ~ 2 64 ** 3 << 2 100 ** NEG 1 - 63 >> 2 64 ** 63 >>").unwrap();
+        assert_eq!(interp.stack(), &[
+            big("-1267650600228229401496703205377"),
+            big("147573952589676412928"),
+            Token::Int(-137438953473),
+            Token::Int(2),
+        ]);
+
+        let msg = "Cannot perform ^ on big ints, only on ints that fit in 64 bits!".to_string();
+        assert_eq!(eval_on(&mut interp, "2 100 ** 1 ^"), Err(StackError::TypeMismatch(msg)));
+    }
+
+    #[test]
+    fn test_bigint_pow_capped() {
+        let mut interp = Interpreter::new();
+        interp.eval("2 200 ** -1 50000000001 ** 0 9999999999 **").unwrap();
+        assert_eq!(interp.stack()[1..], [Token::Int(-1), Token::Int(0)]);
+        assert_eq!(interp.stack()[0].to_string().len(), 61);
+
+        assert!(interp.eval("2 3000000 **").is_err());
+        assert!(interp.eval("10000000000000000000 4000 **").is_err());
+        assert!(BigInt::from(3i64).checked_pow(u64::MAX).is_none());
+    }
+
+}
+
+
+#[cfg(test)]
+mod rational_tests {
+
+    use crate::{bigint::BigInt, lexer::{parse_data_type, read_stack}, rational::Rational, stack::exec_stack, Token};
+
+    fn run(src: &str) -> Vec<String> {
+        let res = exec_stack(&read_stack(src).unwrap()).unwrap();
+        res.iter().map(Token::to_string).collect()
+    }
+
+    #[test]
+    fn test_exact_divide() {
+        assert_eq!(run("1 3 RDIV 1 6 RDIV + 6 -4 RDIV 6 3 RDIV 1 3 /"), vec!["1/2", "-3/2", "2", "0"]);
+        assert_eq!(run("1/3 3 * 2/3 -2 ** 7/2 2 % 1/4 0.5 ** 1/3 NEG"), vec!["1", "9/4", "3/2", "0.5", "-1/3"]);
+        assert_eq!(run("1/3 2/6 == 1/3 1/2 < 1/2 0.5 == 2 1/3 <=>"), vec!["true", "true", "true", "1"]);
+
+        // `//` still starts a comment
+        assert_eq!(run("10 4 RDIV // 5/2"), vec!["5/2"]);
+        assert!(exec_stack(&read_stack("1 0 RDIV").unwrap()).is_err());
+
+        // but not an empty one, which is likely meant as a divide
+        let err = read_stack("1 3 //  \n4").unwrap_err();
+        assert_eq!((err.message.as_str(), err.line, err.col), ("Empty // comment, exact divide is RDIV", 1, 5));
+        assert_eq!(read_stack("{1 | x0 //\n}").unwrap_err().col, 9);
+    }
+
+    #[test]
+    fn test_rational_normalized() {
+        assert_eq!(parse_data_type("4/-6"), None);
+        assert_eq!(parse_data_type("4/2"), Some(Token::Int(2)));
+        assert_eq!(parse_data_type("-4/6").map(|tok| tok.to_string()), Some("-2/3".to_string()));
+        assert_eq!(parse_data_type("1/0"), None);
+
+        let half: Rational = "2/4".parse().unwrap();
+        assert_eq!(half.to_string(), "1/2");
+        assert_eq!(half, Rational::new(BigInt::from(-3i64), BigInt::from(-6i64)).unwrap());
+    }
+
+    #[test]
+    fn test_rational_pow_capped() {
+        assert_eq!(run("2/3 -3 ** -1/2 -3 ** 0 1/3 0 ** 3 100 ** 1/3 100 ** *"), vec!["27/8", "-8", "0", "1", "1"]);
+        assert!(exec_stack(&read_stack("1/3 1000000 **").unwrap()).is_err());
+        assert!(exec_stack(&read_stack("2/3 -9999999999999 **").unwrap()).is_err());
+    }
+
+}
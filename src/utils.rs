@@ -1,69 +1,219 @@
+use std::fmt;
+
+use crate::bigint::BigInt;
+use crate::error::StackError;
+use crate::rational::Rational;
+use crate::lexer::Lexeme;
+
 /// Given a token and a type, extract the value from the
 /// token and convert it to that type!
-pub fn extract_value_from_token<T>(token: Token) -> T
+pub fn extract_value_from_token<T>(token: Token) -> Result<T, StackError>
 where
-    T: From<Token>,
+    T: TryFrom<Token, Error = StackError>,
 {
-    T::from(token)
+    T::try_from(token)
 }
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum Token {
-    Int(i64), Float(f64), Str(String), Bool(bool)
+    Int(i64), BigInt(BigInt), Rational(Box<Rational>), Float(f64), Str(String), Bool(bool), 
+    Vector(Vec<Token>), Matrix(Vec<Vec<Token>>), 
+    Quoted(String), Lambda(Lambda)
+}
+
+/// An anonymous function `{n | body}` that pops n arguments
+/// and binds them to `x0..x(n-1)`, with `x0` the deepest
+#[derive(Clone, Debug)]
+pub struct Lambda {
+    pub arity: usize,
+    pub body: Vec<Lexeme>,
+}
+
+/// Lambdas are equal when they are written the same, wherever
+/// in the source they were written
+impl PartialEq for Lambda {
+    fn eq(&self, other: &Self) -> bool {
+        self.arity == other.arity
+            && self.body.len() == other.body.len()
+            && self.body.iter().zip(&other.body).all(|(a, b)| a.text == b.text)
+    }
 }
 
 impl Token {
     fn ord(&self) -> i8 {
-        match self {
-            &Token::Int(_) => 1,
-            &Token::Float(_) => 2,
-            &Token::Str(_) => 3,
-            &Token::Bool(_) => 4,
+        match *self {
+            Token::Int(_) => 1,
+            Token::BigInt(_) => 2,
+            Token::Rational(_) => 3,
+            Token::Float(_) => 4,
+            Token::Str(_) => 5,
+            Token::Bool(_) => 6,
+            Token::Vector(_) => 7,
+            Token::Matrix(_) => 8,
+            Token::Quoted(_) => 9,
+            Token::Lambda(_) => 10,
         }
     }
 }
 
-impl From<Token> for bool {
-    fn from(token: Token) -> Self {
+impl TryFrom<Token> for bool {
+    type Error = StackError;
+
+    fn try_from(token: Token) -> Result<Self, StackError> {
         if let Token::Bool(val) = token {
-            val
+            Ok(val)
         } else {
-            panic!("Cannot convert {:?} to bool!", token)
+            Err(StackError::TypeMismatch(format!("Cannot convert {} to bool!", token)))
         }
     }
 }
 
-impl From<Token> for i64 {
-    fn from(token: Token) -> Self {
+impl TryFrom<Token> for i64 {
+    type Error = StackError;
+
+    fn try_from(token: Token) -> Result<Self, StackError> {
         if let Token::Int(val) = token {
-            val
+            Ok(val)
         } else {
-            panic!("Cannot convert {:?} to i64!", token)
+            Err(StackError::TypeMismatch(format!("Cannot convert {} to i64!", token)))
         }
     }
 }
 
-impl From<Token> for f64 {
-    fn from(token: Token) -> Self {
+/// Big ints are never demoted here, as `Token::from` has
+/// already made an Int of any that fit
+impl TryFrom<Token> for BigInt {
+    type Error = StackError;
+
+    fn try_from(token: Token) -> Result<Self, StackError> {
         match token {
-            Token::Int(val) => val as f64,
-            Token::Float(val) => val,
-            _ => panic!("Cannot convert {:?} to f64!", token),
+            Token::Int(val) => Ok(BigInt::from(val)),
+            Token::BigInt(val) => Ok(val),
+            _ => Err(StackError::TypeMismatch(format!("Cannot convert {} to a big int!", token))),
         }
     }
 }
 
-impl From<Token> for String {
-    fn from(token: Token) -> Self {
+/// Gives an Int rather than a big int whenever the value fits
+impl From<BigInt> for Token {
+    fn from(val: BigInt) -> Token {
+        match val.to_i64() {
+            Some(val) => Token::Int(val),
+            None => Token::BigInt(val),
+        }
+    }
+}
+
+impl TryFrom<Token> for Rational {
+    type Error = StackError;
+
+    fn try_from(token: Token) -> Result<Self, StackError> {
         match token {
-            Token::Str(val) => val,
-            Token::Int(val) => val.to_string(),
-            Token::Float(val) => val.to_string(),
-            Token::Bool(val) => val.to_string(),
+            Token::Int(val) => Ok(Rational::from(BigInt::from(val))),
+            Token::BigInt(val) => Ok(Rational::from(val)),
+            Token::Rational(val) => Ok(*val),
+            _ => Err(StackError::TypeMismatch(format!("Cannot convert {} to a rational!", token))),
+        }
+    }
+}
+
+/// Gives an integer rather than a rational whenever the
+/// denominator is 1
+impl From<Rational> for Token {
+    fn from(val: Rational) -> Token {
+        if val.is_integer() {
+            Token::from(val.numer().clone())
+        } else {
+            Token::Rational(Box::new(val))
         }
     }
 }
 
+impl TryFrom<Token> for f64 {
+    type Error = StackError;
+
+    fn try_from(token: Token) -> Result<Self, StackError> {
+        match token {
+            Token::Int(val) => Ok(val as f64),
+            Token::BigInt(val) => Ok(val.to_f64()),
+            Token::Rational(val) => Ok(val.to_f64()),
+            Token::Float(val) => Ok(val),
+            _ => Err(StackError::TypeMismatch(format!("Cannot convert {} to f64!", token))),
+        }
+    }
+}
+
+impl TryFrom<Token> for Vec<Token> {
+    type Error = StackError;
+
+    fn try_from(token: Token) -> Result<Self, StackError> {
+        if let Token::Vector(vals) = token {
+            Ok(vals)
+        } else {
+            Err(StackError::TypeMismatch(format!("Cannot convert {} to vector!", token)))
+        }
+    }
+}
+
+impl TryFrom<Token> for Vec<Vec<Token>> {
+    type Error = StackError;
+
+    fn try_from(token: Token) -> Result<Self, StackError> {
+        if let Token::Matrix(rows) = token {
+            Ok(rows)
+        } else {
+            Err(StackError::TypeMismatch(format!("Cannot convert {} to matrix!", token)))
+        }
+    }
+}
+
+/// Converts the token to its value as a string, so strings
+/// are left as they are, i.e. for concatenation
+impl TryFrom<Token> for String {
+    type Error = StackError;
+
+    fn try_from(token: Token) -> Result<Self, StackError> {
+        match token {
+            Token::Str(val) => Ok(val),
+            other => Ok(other.to_string()),
+        }
+    }
+}
+
+/// Renders the token as it would be written in a stack 
+/// file, so strings are quoted and escaped
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Int(val) => write!(f, "{}", val),
+            Token::BigInt(val) => write!(f, "{}", val),
+            Token::Rational(val) => write!(f, "{}", val),
+            Token::Float(val) => write!(f, "{}", val),
+            Token::Str(val) => write!(f, "\"{}\"", escape_string(val)),
+            Token::Bool(val) => write!(f, "{}", val),
+            Token::Vector(vals) => write!(f, "[{}]", join_tokens(vals)),
+            Token::Matrix(rows) => write!(
+                f,
+                "[{}]", 
+                join_tokens(&rows.iter().cloned().map(Token::Vector).collect::<Vec<Token>>())
+            ),
+            Token::Quoted(op) => write!(f, "{}", op),
+            Token::Lambda(lambda) => {
+                let body = lambda.body.iter().map(|lexeme| lexeme.text.as_str()).collect::<Vec<&str>>();
+                write!(f, "{{{} | {}}}", lambda.arity, body.join(" "))
+            },
+        }
+    }
+}
+
+/// Render each token and join them with commas, as used by
+/// vectors and matrices, i.e. `1, 2, 3`
+fn join_tokens(vals: &[Token]) -> String {
+    vals.iter()
+        .map(Token::to_string)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
 
 /// Given two tokens return the token with the greatest precedence 
 /// in the type hierachy 
@@ -77,23 +227,224 @@ pub fn most_generic_type(first: &Token, second: &Token) -> Token {
 
 
 /// given a string from the user, i.e. a value enclosed in quote,
-/// remove the quote characters and convert back to string
-pub fn parse_string(val: &str) -> Result<String, ()> {
-    let chs = val.chars().collect::<Vec<char>>();
-    if val.len() < 2 || chs[0] != '"' || chs[chs.len() - 1] != '"' {
-        Err(())
-    } else {
-        Ok(val.to_string())
+/// remove the quote characters and replace any escape sequences
+pub(crate) fn parse_string(val: &str) -> Result<String, ()> {
+    let inner = val
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or(())?;
+    unescape_string(inner).map_err(|_| ())
+}
+
+
+/// replace the escape sequences `\"`, `\n`, `\t`, `\r`, `\\` and
+/// `\u{..}` in the body of a string, on failure gives the index 
+/// (in chars) of the offending backslash or unescaped quote
+pub fn unescape_string(val: &str) -> Result<String, usize> {
+    let mut res = String::new();
+    let mut chars = val.chars().enumerate();
+
+    while let Some((i, ch)) = chars.next() {
+        match ch {
+            '"' => return Err(i),
+            '\\' => {
+                let escaped = match chars.next().map(|(_, c)| c) {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('u') => {
+                        let mut code = String::new();
+                        loop {
+                            match chars.next() {
+                                Some((_, '}')) => break,
+                                Some((_, c)) => code.push(c),
+                                None => return Err(i),
+                            }
+                        }
+                        code.strip_prefix('{')
+                            .filter(|hex| (1..=6).contains(&hex.len()))
+                            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                            .and_then(char::from_u32)
+                            .ok_or(i)?
+                    },
+                    _ => return Err(i),
+                };
+                res.push(escaped);
+            },
+            c => res.push(c),
+        }
+    }
+    Ok(res)
+}
+
+
+/// the reverse of `unescape_string`, so that the string can
+/// be written back out between quotes
+pub fn escape_string(val: &str) -> String {
+    let mut res = String::new();
+    for ch in val.chars() {
+        match ch {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            '\r' => res.push_str("\\r"),
+            c if c.is_control() => res.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => res.push(c),
+        }
     }
+    res
 }
 
 
 /// given a string from the user, i.e. a value enclosed in quote,
 /// remove the quote characters and convert back to string
-pub fn parse_bool(val: &str) -> Result<bool, ()> {
+pub(crate) fn parse_bool(val: &str) -> Result<bool, ()> {
     match val {
         "true" => Ok(true),
         "false" => Ok(false),
         _ => Err(()),
     }
-}
\ No newline at end of file
+}
+
+
+/// The deepest vector literals may nest, as a matrix is a
+/// vector of vectors and nothing deeper is supported
+const MAX_NESTING: usize = 2;
+
+
+/// given a bracketed literal from the user, i.e. `[1, 2, 3]` or
+/// `[[1, 2], [3, 4]]`, parse it into a vector, or into a matrix
+/// if every element is a row of the same length
+pub(crate) fn parse_bracketed(val: &str) -> Result<Token, ()> {
+    // checked up front, so that each level is only parsed once
+    if nesting(val) > MAX_NESTING {
+        return Err(());
+    }
+    let elements = parse_elements(val)?;
+    if !elements.iter().any(|elem| matches!(elem, Token::Vector(_))) {
+        return Ok(Token::Vector(elements));
+    }
+
+    let rows = elements.into_iter()
+        .map(|elem| match elem {
+            Token::Vector(row) => Ok(row),
+            _ => Err(()),
+        })
+        .collect::<Result<Vec<Vec<Token>>, ()>>()?;
+    if rows.iter().any(|row| row.len() != rows[0].len()) {
+        return Err(());
+    }
+    Ok(Token::Matrix(rows))
+}
+
+
+/// given a bracketed literal that `parse_bracketed` rejected, i.e.
+/// `[[1, 2], [3]]`, explain why it is not a matrix. None if it
+/// does not look like a matrix at all
+pub(crate) fn matrix_error(val: &str) -> Option<String> {
+    let rows = val.strip_prefix('[')?;
+    if !rows.trim_start().starts_with('[') {
+        return None;
+    }
+    if nesting(val) > MAX_NESTING {
+        return Some(format!("Cannot nest vectors more than {} deep!", MAX_NESTING));
+    }
+
+    let elements = parse_elements(val).ok()?;
+    if elements.iter().any(|elem| !matches!(elem, Token::Vector(_))) {
+        return Some("Cannot mix vectors and values in a matrix!".to_string());
+    }
+    let lengths = elements.iter()
+        .map(|elem| match elem {
+            Token::Vector(row) => row.len(),
+            _ => unreachable!("Only vectors are left!"),
+        })
+        .collect::<Vec<usize>>();
+    lengths.iter().find(|&&len| len != lengths[0]).map(|len| format!(
+        "Every row of a matrix must be the same length, found rows of {} and {}!",
+        lengths[0], len
+    ))
+}
+
+
+/// how deeply the brackets of a literal nest, not counting any
+/// within strings
+fn nesting(val: &str) -> usize {
+    let (mut depth, mut deepest) = (0usize, 0);
+    let (mut in_string, mut escaped) = (false, false);
+    for ch in val.chars() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ if in_string => (),
+            '[' => {
+                depth += 1;
+                deepest = deepest.max(depth);
+            },
+            ']' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    deepest
+}
+
+
+/// parse each comma separated element of a bracketed literal
+fn parse_elements(val: &str) -> Result<Vec<Token>, ()> {
+    let inner = val
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or(())?;
+    if inner.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // only split on the commas that are not inside a nested literal
+    // or a string, skipping over escaped characters in strings
+    let mut elements = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    let (mut in_string, mut escaped) = (false, false);
+    for (i, ch) in inner.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ if in_string => (),
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                elements.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    elements.push(&inner[start..]);
+
+    elements.into_iter()
+        .map(|elem| crate::lexer::parse_data_type(elem.trim()).ok_or(()))
+        .collect()
+}
+
+
+/// given a braced literal from the user, i.e. `{2 | x0 x1 +}`,
+/// parse the arity before the bar and lex the body after it
+pub(crate) fn parse_lambda(val: &str) -> Result<Lambda, ()> {
+    let (arity, body) = val
+        .strip_prefix('{')
+        .and_then(|v| v.strip_suffix('}'))
+        .and_then(|v| v.split_once('|'))
+        .ok_or(())?;
+
+    Ok(Lambda {
+        arity: arity.trim().parse::<usize>().map_err(|_| ())?,
+        body: crate::lexer::read_stack(body).map_err(|_| ())?
+            .into_iter()
+            .filter(|lexeme| !lexeme.is_trivia())
+            .collect(),
+    })
+}